@@ -0,0 +1,80 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+
+/// A RAG chatbot that does math with tools and looks words up in a vector store
+#[derive(Parser, Debug)]
+#[command(version)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+
+    /// A TOML config file, `rag.toml` is read when there is one
+    #[arg(long, global = true, env = "RAG_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// The profile sessions start in, overrides `profile`
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+
+    /// The model answering prompts, overrides `provider.completion_model`
+    #[arg(long, global = true)]
+    pub model: Option<String>,
+
+    /// The model embedding documents and queries, overrides `provider.embedding_model`.
+    /// The store has to be rebuilt when it changes
+    #[arg(long, global = true)]
+    pub embedding_model: Option<String>,
+
+    /// Dimensions of the embedding model's vectors, overrides `provider.ndims`. Needed
+    /// along with any embedding model but the provider's default one
+    #[arg(long, global = true)]
+    pub embedding_ndims: Option<usize>,
+
+    /// Sampling temperature of the answering model, overrides the temperature of every
+    /// profile
+    #[arg(long, global = true)]
+    pub temperature: Option<f64>,
+
+    /// Where `ingest` saves the vector store and every other command loads it from,
+    /// overrides `data.store`. Without one, only the built-in glossary is searchable
+    #[arg(long, global = true)]
+    pub store: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Chunks and embeds files into the vector store, re-ingesting a file replaces its
+    /// chunks
+    Ingest {
+        /// Markdown or plain text files, `data.sources` if none are given
+        files: Vec<PathBuf>,
+    },
+    /// Prints the documents closest to a query, with their scores
+    Search {
+        query: String,
+        /// How many documents to print
+        #[arg(short = 'n', long, default_value_t = 5)]
+        top: usize,
+    },
+    /// Answers a single prompt
+    Ask {
+        prompt: String,
+        /// Continues the session saved in this file, and saves it back afterwards
+        #[arg(long)]
+        session: Option<PathBuf>,
+    },
+    /// Starts an interactive session
+    Chat,
+    /// Runs the scenarios of a suite and reports which ones failed
+    Eval {
+        /// A JSON suite, see `evals/demo.json`
+        suite: PathBuf,
+    },
+    /// Lists what is remembered about the user
+    Memories {
+        /// Forgets a memory by id, or every memory with "all"
+        #[arg(long)]
+        forget: Option<String>,
+    },
+}
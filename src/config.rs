@@ -0,0 +1,811 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Deserializer};
+
+use crate::{cli::Cli, tools, utils};
+
+/// read when `--config` isn't given, if it exists
+const DEFAULT_PATH: &str = "rag.toml";
+
+pub const ASSISTANT_PREAMBLE: &str = "You are a helpful assistant. All algebraic operations must use the tools at your disposal. The \"lookup\" tool can not only be used to look up the definition of a word, but also to find any and all information regarding that word or concept. Use the \"lookup\" tool thoroughly to ensure you get the most accurate and relevant information. However, if you believe the information you are looking for is already in your context, do not use the \"lookup\" tool. The math tools return an exact \"result\", to be used in further calculations, and a rounded \"display\" value, to be used when presenting the result to the user. If none of the offered math tools fits an operation, use the \"calculate\" tool. Use the \"convert\" tool for unit and currency conversions instead of multiplying by a rate yourself. Use the \"ledger\" tool to keep track of how much of something the user has as they receive or give it away. Use the \"remember\" tool when the user tells you what a word means, so it can be looked up later.";
+
+pub const CALCULATOR_PREAMBLE: &str = "You are a strict calculator. Only answer questions that need a calculation, and politely refuse anything else. Every operation must go through the tools at your disposal, never compute anything yourself. The math tools return an exact \"result\", to be used in further calculations, and a rounded \"display\" value, to be used when presenting the result to the user. If none of the offered math tools fits an operation, use the \"calculate\" tool. Use the \"convert\" tool for unit and currency conversions. Answer with the result and the operations that led to it, nothing more.";
+
+pub const GLOSSARY_PREAMBLE: &str = "You explain the meaning of words and concepts using the knowledge base. Use the \"lookup\" tool to find what a word means, unless it is already in your context, and only answer from what you find, saying so when the knowledge base doesn't know a word. Do not perform calculations. Use the \"remember\" tool when the user tells you what a word means, so it can be looked up later.";
+
+/// the tools an agent can be given besides the single-operation math ones
+const STATIC_TOOLS: [&str; 5] = ["calculate", "convert", "ledger", "lookup", "remember"];
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("Could not read {}: {source}", .path.display())]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("Invalid config file {}: {source}", .path.display())]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("Invalid value `{value}` for {name}: {reason}")]
+    Env {
+        name: String,
+        value: String,
+        reason: String,
+    },
+    #[error("Invalid `{field}`: {reason}")]
+    Invalid { field: String, reason: String },
+}
+
+/// everything `main` used to hard-code. every field has a default, so a config file
+/// only needs the ones it changes
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub provider: ProviderConfig,
+    pub usage: UsageConfig,
+    pub math: MathConfig,
+    /// the profile sessions start in
+    pub profile: String,
+    /// `assistant`, `calculator` and `glossary` are built in, a `[profiles.<name>]` table
+    /// with one of their names only changes the fields it sets
+    #[serde(deserialize_with = "merge_profiles")]
+    pub profiles: BTreeMap<String, ProfileConfig>,
+    pub retrieval: RetrievalConfig,
+    pub data: DataConfig,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderKind {
+    #[default]
+    Gemini,
+    /// any server speaking the OpenAI HTTP API
+    #[serde(rename = "openai")]
+    OpenAi,
+    /// a local Ollama, through its OpenAI-compatible endpoints
+    Ollama,
+}
+
+impl ProviderKind {
+    /// completion model, embedding model and its dimensions
+    fn default_models(self) -> (&'static str, &'static str, usize) {
+        match self {
+            Self::Gemini => ("gemini-2.0-flash", "text-embedding-004", 768),
+            Self::OpenAi => ("gpt-4o-mini", "text-embedding-3-small", 1536),
+            Self::Ollama => ("llama3.2", "nomic-embed-text", 768),
+        }
+    }
+
+    fn default_base_url(self) -> Option<&'static str> {
+        match self {
+            Self::Gemini => None,
+            Self::OpenAi => Some("https://api.openai.com/v1"),
+            Self::Ollama => Some("http://localhost:11434/v1"),
+        }
+    }
+
+    fn default_api_key_env(self) -> Option<&'static str> {
+        match self {
+            Self::Gemini => Some("GEMINI_API_KEY"),
+            Self::OpenAi => Some("OPENAI_API_KEY"),
+            Self::Ollama => None,
+        }
+    }
+}
+
+impl FromStr for ProviderKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gemini" => Ok(Self::Gemini),
+            "openai" => Ok(Self::OpenAi),
+            "ollama" => Ok(Self::Ollama),
+            _ => Err("expected gemini, openai or ollama".to_string()),
+        }
+    }
+}
+
+/// where embeddings come from
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum EmbeddingKind {
+    /// the embedding model of the provider
+    #[default]
+    Provider,
+    /// `utils::HashingEmbeddingModel`, computed in process without any network
+    Local,
+}
+
+impl FromStr for EmbeddingKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "provider" => Ok(Self::Provider),
+            "local" => Ok(Self::Local),
+            _ => Err("expected provider or local".to_string()),
+        }
+    }
+}
+
+/// the fields left out default to the kind's usual values, see the accessors
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProviderConfig {
+    pub kind: ProviderKind,
+    pub embeddings: EmbeddingKind,
+    pub completion_model: Option<String>,
+    pub embedding_model: Option<String>,
+    /// dimensions of the embeddings, the store has to be rebuilt when it changes
+    pub ndims: Option<usize>,
+    /// only for `openai` and `ollama`
+    pub base_url: Option<String>,
+    /// the environment variable holding the API key
+    pub api_key_env: Option<String>,
+}
+
+impl ProviderConfig {
+    pub fn completion_model(&self) -> &str {
+        self.completion_model
+            .as_deref()
+            .unwrap_or(self.kind.default_models().0)
+    }
+
+    /// the name usage is tracked under with local embeddings
+    pub fn embedding_model(&self) -> &str {
+        match self.embeddings {
+            EmbeddingKind::Provider => self
+                .embedding_model
+                .as_deref()
+                .unwrap_or(self.kind.default_models().1),
+            EmbeddingKind::Local => utils::HASHING_MODEL_NAME,
+        }
+    }
+
+    pub fn ndims(&self) -> usize {
+        self.ndims.unwrap_or(match self.embeddings {
+            EmbeddingKind::Provider => self.kind.default_models().2,
+            EmbeddingKind::Local => utils::HashingEmbeddingModel::DEFAULT_NDIMS,
+        })
+    }
+
+    pub fn base_url(&self) -> Option<&str> {
+        self.base_url.as_deref().or(self.kind.default_base_url())
+    }
+
+    /// `None` for a local Ollama, which doesn't need a key
+    pub fn api_key_env(&self) -> Option<&str> {
+        self.api_key_env
+            .as_deref()
+            .or(self.kind.default_api_key_env())
+    }
+}
+
+/// what the models cost and how much a session may spend
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct UsageConfig {
+    /// in USD per million tokens, models missing from it are tracked but not priced
+    pub prices: BTreeMap<String, utils::ModelPrice>,
+    /// the session stops once it goes over either, `None` doesn't limit it
+    pub max_tokens: Option<u64>,
+    /// in USD
+    pub max_cost: Option<f64>,
+}
+
+impl UsageConfig {
+    pub fn price_table(&self) -> utils::PriceTable {
+        self.prices
+            .iter()
+            .fold(utils::PriceTable::new(), |table, (model, price)| {
+                table.with_model(model, *price)
+            })
+    }
+
+    pub fn budget(&self) -> utils::Budget {
+        utils::Budget {
+            max_tokens: self.max_tokens,
+            max_cost: self.max_cost,
+        }
+    }
+}
+
+impl Default for UsageConfig {
+    fn default() -> Self {
+        Self {
+            prices: BTreeMap::from([(
+                "gemini-2.0-flash".to_string(),
+                utils::ModelPrice {
+                    prompt: 0.10,
+                    completion: 0.40,
+                    embedding: 0.0,
+                },
+            )]),
+            max_tokens: None,
+            max_cost: Some(0.10),
+        }
+    }
+}
+
+/// how the math tools, the ledger and the conversions compute and show their results
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct MathConfig {
+    pub backend: tools::Backend,
+    /// decimal places of the displayed values, results are kept exact
+    pub places: u32,
+    pub rounding: tools::RoundingMode,
+}
+
+impl MathConfig {
+    pub fn context(&self) -> tools::MathContext {
+        let context = match self.backend {
+            tools::Backend::Float => tools::MathContext::default(),
+            tools::Backend::Decimal => tools::MathContext::decimal(),
+        };
+        context
+            .with_scale(Some(self.places))
+            .with_rounding(self.rounding)
+    }
+}
+
+impl Default for MathConfig {
+    fn default() -> Self {
+        Self {
+            backend: tools::Backend::Decimal,
+            places: 2,
+            rounding: tools::RoundingMode::default(),
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            provider: ProviderConfig::default(),
+            usage: UsageConfig::default(),
+            math: MathConfig::default(),
+            profile: "assistant".to_string(),
+            profiles: builtin_profiles(),
+            retrieval: RetrievalConfig::default(),
+            data: DataConfig::default(),
+        }
+    }
+}
+
+/// what a `MultiTurnAgent` profile's agent is built with
+#[derive(Debug, Clone)]
+pub struct ProfileConfig {
+    pub preamble: String,
+    pub temperature: f64,
+    /// the only tools the agent is given, single-operation math tools included
+    pub tools: Vec<String>,
+}
+
+impl ProfileConfig {
+    pub fn has_tool(&self, name: &str) -> bool {
+        self.tools.iter().any(|tool| tool == name)
+    }
+}
+
+/// a `[profiles.<name>]` table, the fields it leaves out come from the built-in profile
+/// of the same name, or from `assistant`. a new profile only gets the tools it lists
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PartialProfile {
+    preamble: Option<String>,
+    temperature: Option<f64>,
+    tools: Option<Vec<String>>,
+}
+
+fn builtin_profiles() -> BTreeMap<String, ProfileConfig> {
+    let tools = |names: &[&str]| -> Vec<String> {
+        names
+            .iter()
+            .chain(tools::MATH_TOOLS)
+            .map(|name| name.to_string())
+            .collect()
+    };
+
+    BTreeMap::from([
+        (
+            "assistant".to_string(),
+            ProfileConfig {
+                preamble: ASSISTANT_PREAMBLE.to_string(),
+                temperature: 0.0,
+                tools: tools(&STATIC_TOOLS),
+            },
+        ),
+        (
+            "calculator".to_string(),
+            ProfileConfig {
+                preamble: CALCULATOR_PREAMBLE.to_string(),
+                temperature: 0.0,
+                tools: tools(&["calculate", "convert", "ledger"]),
+            },
+        ),
+        (
+            "glossary".to_string(),
+            ProfileConfig {
+                preamble: GLOSSARY_PREAMBLE.to_string(),
+                temperature: 0.0,
+                tools: vec!["lookup".to_string(), "remember".to_string()],
+            },
+        ),
+    ])
+}
+
+fn merge_profiles<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<String, ProfileConfig>, D::Error> {
+    let partials = BTreeMap::<String, PartialProfile>::deserialize(deserializer)?;
+    let mut profiles = builtin_profiles();
+    for (name, partial) in partials {
+        let base = match profiles.get(&name) {
+            Some(builtin) => builtin.clone(),
+            // tools are only ever granted explicitly
+            None => ProfileConfig {
+                tools: Vec::new(),
+                ..profiles["assistant"].clone()
+            },
+        };
+        let profile = ProfileConfig {
+            preamble: partial.preamble.unwrap_or(base.preamble),
+            temperature: partial.temperature.unwrap_or(base.temperature),
+            tools: partial.tools.unwrap_or(base.tools),
+        };
+        profiles.insert(name, profile);
+    }
+    Ok(profiles)
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetrievalConfig {
+    /// glossary entries and chunks added to every prompt
+    pub context_top_k: usize,
+    /// memories added to every prompt
+    pub memory_top_k: usize,
+    /// single-operation math tools offered on every prompt
+    pub tool_top_k: usize,
+    /// map the sentences of the answer to the documents supporting them
+    pub citations: bool,
+    /// how similar a sentence of the answer has to be to a document to cite it
+    pub citation_threshold: f64,
+}
+
+impl Default for RetrievalConfig {
+    fn default() -> Self {
+        Self {
+            context_top_k: 1,
+            memory_top_k: 2,
+            tool_top_k: 4,
+            citations: true,
+            citation_threshold: 0.6,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct DataConfig {
+    /// where `ingest` saves the vector store and every other command loads it from
+    pub store: PathBuf,
+    /// files `ingest` reads when none are given
+    pub sources: Vec<PathBuf>,
+    /// in bytes of UTF-8, a sentence longer than that is cut
+    pub chunk_size: usize,
+    /// in sentences for markdown and plain text files, other files are cut in fixed
+    /// windows overlapping by a tenth of `chunk_size`
+    pub chunk_overlap: usize,
+    /// a JSON file of extra conversion units
+    pub units_file: Option<PathBuf>,
+    /// entries written by the `remember` tool wait for `/approve` in `chat` before they
+    /// can be found
+    pub review_knowledge: bool,
+    /// whose memories are read and written
+    pub user: String,
+    pub memory_dir: PathBuf,
+    /// where the readline history of `chat` is kept
+    pub history_file: PathBuf,
+}
+
+impl Default for DataConfig {
+    fn default() -> Self {
+        Self {
+            store: PathBuf::from("store.json"),
+            sources: Vec::new(),
+            chunk_size: 1000,
+            chunk_overlap: 1,
+            units_file: None,
+            review_knowledge: false,
+            user: "default".to_string(),
+            memory_dir: PathBuf::from("memories"),
+            history_file: PathBuf::from(".rag_history"),
+        }
+    }
+}
+
+impl Config {
+    /// reads the config file (`--config`, or `rag.toml` if there is one), then applies the
+    /// `RAG_*` environment variables and the command-line options on top, and validates
+    /// the result
+    pub fn load(cli: &Cli) -> Result<Self, ConfigError> {
+        let mut config = match &cli.config {
+            Some(path) => Self::read(path)?,
+            None if Path::new(DEFAULT_PATH).exists() => Self::read(DEFAULT_PATH)?,
+            None => Self::default(),
+        };
+        config.apply_env()?;
+
+        if let Some(model) = &cli.model {
+            config.provider.completion_model = Some(model.clone());
+        }
+        if let Some(model) = &cli.embedding_model {
+            config.provider.embedding_model = Some(model.clone());
+        }
+        if let Some(ndims) = cli.embedding_ndims {
+            config.provider.ndims = Some(ndims);
+        }
+        if let Some(profile) = &cli.profile {
+            config.profile = profile.clone();
+        }
+        if let Some(temperature) = cli.temperature {
+            for profile in config.profiles.values_mut() {
+                profile.temperature = temperature;
+            }
+        }
+        if let Some(store) = &cli.store {
+            config.data.store = store.clone();
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn read(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|source| ConfigError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        toml::from_str(&text).map_err(|source| ConfigError::Parse {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    fn apply_env(&mut self) -> Result<(), ConfigError> {
+        env("RAG_PROVIDER", &mut self.provider.kind)?;
+        env("RAG_EMBEDDINGS", &mut self.provider.embeddings)?;
+        optional_env("RAG_MODEL", &mut self.provider.completion_model)?;
+        optional_env("RAG_EMBEDDING_MODEL", &mut self.provider.embedding_model)?;
+        optional_env("RAG_EMBEDDING_NDIMS", &mut self.provider.ndims)?;
+        optional_env("RAG_BASE_URL", &mut self.provider.base_url)?;
+        env("RAG_PROFILE", &mut self.profile)?;
+        env("RAG_CONTEXT_TOP_K", &mut self.retrieval.context_top_k)?;
+        env("RAG_STORE", &mut self.data.store)?;
+        env("RAG_USER", &mut self.data.user)?;
+        env("RAG_MEMORY_DIR", &mut self.data.memory_dir)?;
+        env("RAG_HISTORY_FILE", &mut self.data.history_file)?;
+
+        if let Ok(path) = std::env::var("RAG_UNITS_FILE") {
+            self.data.units_file = Some(PathBuf::from(path));
+        }
+        // both apply to every profile
+        let temperature: Option<f64> = parse_env("RAG_TEMPERATURE")?;
+        // comma-separated
+        let tools = std::env::var("RAG_TOOLS").ok().map(|tools| {
+            tools
+                .split(',')
+                .map(|tool| tool.trim().to_string())
+                .filter(|tool| !tool.is_empty())
+                .collect::<Vec<_>>()
+        });
+        for profile in self.profiles.values_mut() {
+            if let Some(temperature) = temperature {
+                profile.temperature = temperature;
+            }
+            if let Some(tools) = &tools {
+                profile.tools = tools.clone();
+            }
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |field: &str, reason: &str| ConfigError::Invalid {
+            field: field.to_string(),
+            reason: reason.to_string(),
+        };
+
+        for (field, value) in [
+            (
+                "provider.completion_model",
+                self.provider.completion_model(),
+            ),
+            ("provider.embedding_model", self.provider.embedding_model()),
+            ("data.user", &self.data.user),
+        ] {
+            if value.trim().is_empty() {
+                return Err(invalid(field, "must not be empty"));
+            }
+        }
+        if self.provider.ndims() == 0 {
+            return Err(invalid("provider.ndims", "must be positive"));
+        }
+        // the default dimensions only fit the default model, a wrong size is only noticed
+        // by the provider, or not at all
+        if self.provider.embeddings == EmbeddingKind::Provider
+            && self.provider.ndims.is_none()
+            && self.provider.embedding_model() != self.provider.kind.default_models().1
+        {
+            return Err(invalid(
+                "provider.ndims",
+                &format!(
+                    "is required with the embedding model `{}`",
+                    self.provider.embedding_model()
+                ),
+            ));
+        }
+        if self.provider.embeddings == EmbeddingKind::Local
+            && self.provider.embedding_model.is_some()
+        {
+            return Err(invalid(
+                "provider.embedding_model",
+                "local embeddings don't use one",
+            ));
+        }
+        if self.provider.kind == ProviderKind::Gemini && self.provider.base_url.is_some() {
+            return Err(invalid(
+                "provider.base_url",
+                "only openai and ollama take one",
+            ));
+        }
+        for (model, price) in &self.usage.prices {
+            if [price.prompt, price.completion, price.embedding]
+                .iter()
+                .any(|price| !price.is_finite() || *price < 0.0)
+            {
+                return Err(invalid(
+                    &format!("usage.prices.{model}"),
+                    "prices must not be negative",
+                ));
+            }
+        }
+        if self
+            .usage
+            .max_cost
+            .is_some_and(|cost| !cost.is_finite() || cost < 0.0)
+        {
+            return Err(invalid("usage.max_cost", "must not be negative"));
+        }
+        if !self.profiles.contains_key(&self.profile) {
+            return Err(invalid(
+                "profile",
+                &format!("there is no profile `{}`", self.profile),
+            ));
+        }
+        for (name, profile) in &self.profiles {
+            if profile.preamble.trim().is_empty() {
+                return Err(invalid(
+                    &format!("profiles.{name}.preamble"),
+                    "must not be empty",
+                ));
+            }
+            if !(0.0..=2.0).contains(&profile.temperature) {
+                return Err(invalid(
+                    &format!("profiles.{name}.temperature"),
+                    "must be between 0 and 2",
+                ));
+            }
+            if let Some(tool) = profile.tools.iter().find(|tool| {
+                !STATIC_TOOLS.contains(&tool.as_str())
+                    && !tools::MATH_TOOLS.contains(&tool.as_str())
+            }) {
+                return Err(invalid(
+                    &format!("profiles.{name}.tools"),
+                    &format!("there is no tool `{tool}`"),
+                ));
+            }
+        }
+        if !(0.0..=1.0).contains(&self.retrieval.citation_threshold) {
+            return Err(invalid(
+                "retrieval.citation_threshold",
+                "must be between 0 and 1",
+            ));
+        }
+        if self.data.chunk_size == 0 {
+            return Err(invalid("data.chunk_size", "must be positive"));
+        }
+        Ok(())
+    }
+}
+
+/// replaces `target` with the value of the environment variable `name`, if it is set
+fn env<T>(name: &str, target: &mut T) -> Result<(), ConfigError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    if let Some(value) = parse_env(name)? {
+        *target = value;
+    }
+    Ok(())
+}
+
+/// same as `env`, for settings that are optional in the file
+fn optional_env<T>(name: &str, target: &mut Option<T>) -> Result<(), ConfigError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    if let Some(value) = parse_env(name)? {
+        *target = Some(value);
+    }
+    Ok(())
+}
+
+fn parse_env<T>(name: &str) -> Result<Option<T>, ConfigError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let Ok(value) = std::env::var(name) else {
+        return Ok(None);
+    };
+    value
+        .parse()
+        .map(Some)
+        .map_err(|err: T::Err| ConfigError::Env {
+            name: name.to_string(),
+            value: value.clone(),
+            reason: err.to_string(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Config {
+        toml::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn env_overrides_the_file() {
+        let mut config = parse(
+            r#"
+            [retrieval]
+            context_top_k = 3
+
+            [data]
+            user = "from-file"
+            "#,
+        );
+        // the only test reading the environment, so nothing else sees these
+        unsafe {
+            std::env::set_var("RAG_CONTEXT_TOP_K", "5");
+            std::env::set_var("RAG_TEMPERATURE", "0.7");
+        }
+        let applied = config.apply_env();
+        unsafe {
+            std::env::remove_var("RAG_CONTEXT_TOP_K");
+            std::env::remove_var("RAG_TEMPERATURE");
+        }
+        applied.unwrap();
+
+        assert_eq!(config.retrieval.context_top_k, 5);
+        assert_eq!(config.data.user, "from-file");
+        assert!(
+            config
+                .profiles
+                .values()
+                .all(|profile| profile.temperature == 0.7)
+        );
+    }
+
+    #[test]
+    fn profiles_only_change_the_fields_they_set() {
+        let config = parse(
+            r#"
+            [profiles.calculator]
+            temperature = 0.5
+
+            [profiles.auditor]
+            preamble = "Check every number."
+            "#,
+        );
+
+        let calculator = &config.profiles["calculator"];
+        assert_eq!(calculator.temperature, 0.5);
+        assert_eq!(calculator.preamble, CALCULATOR_PREAMBLE);
+        assert!(calculator.has_tool("ledger"));
+        // new profiles start from `assistant`
+        let auditor = &config.profiles["auditor"];
+        assert_eq!(auditor.preamble, "Check every number.");
+        assert_eq!(
+            auditor.temperature,
+            config.profiles["assistant"].temperature
+        );
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn unknown_profiles_are_rejected() {
+        let config = parse(r#"profile = "accountant""#);
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::Invalid { field, .. }) if field == "profile"
+        ));
+    }
+
+    #[test]
+    fn out_of_range_values_are_rejected() {
+        let cases = [
+            (
+                "[profiles.assistant]\ntemperature = 2.5",
+                "profiles.assistant.temperature",
+            ),
+            (
+                "[retrieval]\ncitation_threshold = 1.5",
+                "retrieval.citation_threshold",
+            ),
+            ("[data]\nchunk_size = 0", "data.chunk_size"),
+            ("[provider]\nndims = 0", "provider.ndims"),
+            ("[usage]\nmax_cost = -1.0", "usage.max_cost"),
+            (
+                "[profiles.glossary]\ntools = [\"teleport\"]",
+                "profiles.glossary.tools",
+            ),
+        ];
+        for (text, expected) in cases {
+            match parse(text).validate() {
+                Err(ConfigError::Invalid { field, .. }) => assert_eq!(field, expected),
+                other => panic!("{text}: expected {expected} to be invalid, got {other:?}"),
+            }
+        }
+        assert!(toml::from_str::<Config>("[data]\nchunk = 10").is_err());
+        assert!(Config::default().validate().is_ok());
+    }
+
+    #[test]
+    fn new_profiles_get_no_tools_unless_they_list_them() {
+        let config = parse(
+            r#"
+            [profiles.auditor]
+            preamble = "Check every number."
+            "#,
+        );
+
+        let auditor = &config.profiles["auditor"];
+        assert!(auditor.tools.is_empty());
+        let math_tools =
+            tools::math_toolset(tools::MathContext::decimal(), |name| auditor.has_tool(name));
+        assert!(
+            !tools::MATH_TOOLS
+                .iter()
+                .any(|name| math_tools.contains(name))
+        );
+        assert!(!STATIC_TOOLS.iter().any(|name| auditor.has_tool(name)));
+    }
+
+    #[test]
+    fn example_config_is_valid() {
+        let config = parse(include_str!("../rag.example.toml"));
+        assert!(config.validate().is_ok());
+        assert_eq!(config.profile, "assistant");
+        assert_eq!(
+            config.profiles["assistant"].tools,
+            Config::default().profiles["assistant"].tools
+        );
+    }
+}
@@ -0,0 +1,140 @@
+use std::path::Path;
+
+use rig::completion::CompletionModel;
+use serde::Deserialize;
+
+use crate::utils::{ApprovalDecision, MultiTurnAgent, ReportsUsage, TurnOutcome};
+
+/// scenarios run one after the other, each in a fresh session
+#[derive(Deserialize, Debug)]
+pub struct Suite {
+    pub scenarios: Vec<Scenario>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Scenario {
+    pub name: String,
+    pub prompt: String,
+    /// the profile to answer in, the one the agent was in when the suite started if `None`
+    pub profile: Option<String>,
+    #[serde(default)]
+    pub expect: Expectations,
+    /// whether the calls needing approval are approved or denied
+    #[serde(default = "approve_by_default")]
+    pub approve: bool,
+}
+
+fn approve_by_default() -> bool {
+    true
+}
+
+/// every check is optional, a scenario with none only has to answer without an error
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct Expectations {
+    /// texts the answer has to contain, ignoring case
+    pub contains: Vec<String>,
+    /// tools that have to be called at least once
+    pub tools: Vec<String>,
+    /// documents the answer has to be based on
+    pub sources: Vec<String>,
+    pub max_turns: Option<usize>,
+}
+
+pub struct ScenarioReport {
+    pub name: String,
+    /// empty if the scenario passed
+    pub failures: Vec<String>,
+}
+
+impl Suite {
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub async fn run<M>(&self, agent: &mut MultiTurnAgent<M>) -> Vec<ScenarioReport>
+    where
+        M: CompletionModel,
+        M::Response: ReportsUsage,
+    {
+        let mut reports = Vec::new();
+        let initial = agent.profile().to_string();
+        for scenario in &self.scenarios {
+            agent.clear_history().await;
+            let profile = scenario.profile.as_deref().unwrap_or(&initial);
+            let failures = match scenario.answer(agent, profile).await {
+                Ok(outcome) => scenario.expect.check(&outcome),
+                Err(err) => vec![format!("turn failed: {err}")],
+            };
+            reports.push(ScenarioReport {
+                name: scenario.name.clone(),
+                failures,
+            });
+        }
+        agent
+            .set_profile(&initial)
+            .await
+            .expect("the initial profile exists");
+        agent.clear_history().await;
+        reports
+    }
+}
+
+impl Scenario {
+    async fn answer<M>(
+        &self,
+        agent: &mut MultiTurnAgent<M>,
+        profile: &str,
+    ) -> anyhow::Result<TurnOutcome>
+    where
+        M: CompletionModel,
+        M::Response: ReportsUsage,
+    {
+        agent.set_profile(profile).await?;
+        let mut outcome = agent.multi_turn_prompt(&self.prompt).await?;
+        while outcome.pending_approval.is_some() {
+            let decision = if self.approve {
+                ApprovalDecision::Approve
+            } else {
+                ApprovalDecision::deny("denied by the eval scenario")
+            };
+            outcome = agent.resume(decision).await?;
+        }
+        Ok(outcome)
+    }
+}
+
+impl Expectations {
+    fn check(&self, outcome: &TurnOutcome) -> Vec<String> {
+        let mut failures = Vec::new();
+
+        let text = outcome.text.to_lowercase();
+        for expected in &self.contains {
+            if !text.contains(&expected.to_lowercase()) {
+                failures.push(format!("answer doesn't contain \"{expected}\""));
+            }
+        }
+        for tool in &self.tools {
+            if !outcome.tool_calls.iter().any(|call| &call.name == tool) {
+                failures.push(format!("`{tool}` wasn't called"));
+            }
+        }
+        let sources = outcome.provenance.document_ids();
+        for source in &self.sources {
+            if !sources.contains(&source.as_str()) {
+                failures.push(format!("{source} isn't among the sources"));
+            }
+        }
+        if let Some(max_turns) = self
+            .max_turns
+            .filter(|&max_turns| outcome.turns > max_turns)
+        {
+            failures.push(format!(
+                "took {} turns, more than {max_turns}",
+                outcome.turns
+            ));
+        }
+
+        failures
+    }
+}
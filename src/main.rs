@@ -1,104 +1,566 @@
-use rig::providers::gemini;
-use rig::{Embed, providers::gemini::completion::gemini_api_types::GenerationConfig};
+use rig::agent::{Agent, AgentBuilder};
+use rig::embeddings::EmbeddingModel;
 
 use anyhow::Result;
-use rig::vector_store::in_memory_store::InMemoryVectorStore;
-use serde::{Deserialize, Serialize};
+use clap::Parser;
+use rig::vector_store::{VectorStoreIndex, in_memory_store::InMemoryVectorStore};
+use std::time::Duration;
 
+mod cli;
+mod config;
+mod eval;
+mod provider;
+mod repl;
 mod tools;
 mod utils;
 
-#[derive(Embed, Deserialize, Serialize, Clone, Debug, Eq, PartialEq, Default)]
-struct WordDefinition {
-    id: String,
-    word: String,
-    #[embed]
-    definitions: Vec<String>,
-}
+type Store<E> = utils::VectorStore<utils::KnowledgeDocument, E>;
 
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
-    env_logger::init();
-
-    let client = gemini::Client::from_env();
-
-    let embedding_model = client.embedding_model_with_ndims("text-embedding-004", 768);
-
-    let embeddings = utils::embed(embedding_model.clone(),
-        vec![
-            WordDefinition {
-                id: "doc0".to_string(),
-                word: "flurbo".to_string(),
-                definitions: vec![
-                    "1. *flurbo* (name): A flurbo is a green alien that lives on cold planets.".to_string(),
-                    "2. *flurbo* (name): A fictional digital currency that originated in the animated series Rick and Morty. Each flurbo is worth 10 USD, and you can have and/or give away a fraction of a flurbo (0.3 flurbos, for example).".to_string()
-                ]
-            },
-            WordDefinition {
-                id: "doc1".to_string(),
-                word: "glarb-glarb".to_string(),
-                definitions: vec![
-                    "1. *glarb-glarb* (noun): A glarb-glarb is a ancient tool used by the ancestors of the inhabitants of planet Jiro to farm the land.".to_string(),
-                    "2. *glarb-glarb* (noun): A fictional creature found in the distant, swampy marshlands of the planet Glibbo in the Andromeda galaxy.".to_string()
-                ]
-            },
-            WordDefinition {
-                id: "doc2".to_string(),
-                word: "linglingdong".to_string(),
-                definitions: vec![
-                    "1. *linglingdong* (noun): A term used by inhabitants of the far side of the moon to describe humans.".to_string(),
-                    "2. *linglingdong* (noun): A rare, mystical instrument crafted by the ancient monks of the Nebulon Mountain Ranges on the planet Quarm.".to_string()
-                ]
-            },
-        ])
+    let cli = cli::Cli::parse();
+    let config = config::Config::load(&cli)?;
+
+    // the chat only shows warnings, so logs don't drown the conversation
+    let default_filter = match cli.command {
+        cli::Command::Chat => "rag_tool_test=warn",
+        _ => "rag_tool_test=info",
+    };
+    utils::init_tracing(utils::LogFormat::from_env(), default_filter)?;
+
+    match config.provider.kind {
+        config::ProviderKind::Gemini => {
+            start(&cli, &config, provider::Gemini::new(&config.provider)).await
+        }
+        config::ProviderKind::OpenAi | config::ProviderKind::Ollama => {
+            start(
+                &cli,
+                &config,
+                provider::OpenAiCompatible::new(&config.provider),
+            )
+            .await
+        }
+    }
+}
+
+/// everything past here is the same for every provider and kind of embeddings. with local
+/// embeddings, a provider that failed to set up only fails the commands needing completions
+async fn start<P: provider::Provider>(
+    cli: &cli::Cli,
+    config: &config::Config,
+    provider: Result<P>,
+) -> Result<()> {
+    match config.provider.embeddings {
+        config::EmbeddingKind::Provider => run(cli, config, provider?).await,
+        config::EmbeddingKind::Local => run(cli, config, provider::LocalEmbeddings(provider)).await,
+    }
+}
+
+async fn run<P: provider::Provider>(
+    cli: &cli::Cli,
+    config: &config::Config,
+    provider: P,
+) -> Result<()> {
+    let embedding_model =
+        provider.embedding_model(config.provider.embedding_model(), config.provider.ndims());
+
+    let usage =
+        utils::UsageTracker::new(config.usage.price_table()).with_budget(config.usage.budget());
+
+    match &cli.command {
+        cli::Command::Ingest { files } => {
+            let files = if files.is_empty() {
+                &config.data.sources
+            } else {
+                files
+            };
+            if files.is_empty() {
+                anyhow::bail!("Nothing to ingest, pass files or set `data.sources`");
+            }
+
+            let store = open_store(config, embedding_model.clone(), &usage).await?;
+            for path in files {
+                let chunks = utils::Chunker::for_path(
+                    path,
+                    config.data.chunk_size,
+                    config.data.chunk_overlap,
+                )
+                .chunk_file(path)?;
+                // chunk ids are positional, so a file that got shorter would leave stale
+                // chunks behind
+                let source = path.display().to_string();
+                store.retain(|_, document| match document {
+                    utils::KnowledgeDocument::Chunk(chunk) => chunk.source != source,
+                    utils::KnowledgeDocument::Word(_) => true,
+                });
+
+                let documents = chunks
+                    .into_iter()
+                    .map(utils::KnowledgeDocument::Chunk)
+                    .collect();
+                let embeddings = store.embed(documents).await?;
+                println!("Ingested {} chunks from {}", embeddings.len(), source);
+                for (document, embedding) in embeddings {
+                    let id = match &document {
+                        utils::KnowledgeDocument::Word(word) => word.id.clone(),
+                        utils::KnowledgeDocument::Chunk(chunk) => chunk.id.clone(),
+                    };
+                    store.upsert(id, document, embedding);
+                }
+            }
+            store.save(&config.data.store)?;
+            println!("Saved the vector store to {}", config.data.store.display());
+            print_usage(&usage);
+        }
+        cli::Command::Search { query, top } => {
+            let store = open_store(config, embedding_model.clone(), &usage).await?;
+            let results = store
+                .index()
+                .top_n::<utils::KnowledgeDocument>(query, *top)
+                .await?;
+            for (score, id, document) in results {
+                let text = match document {
+                    utils::KnowledgeDocument::Word(word) => {
+                        format!("{}: {}", word.word, word.definitions.join(" "))
+                    }
+                    utils::KnowledgeDocument::Chunk(chunk) => chunk.text,
+                };
+                println!("{score:.3} [{id}] {}", repl::truncate(&text, 200));
+            }
+        }
+        cli::Command::Ask { prompt, session } => {
+            let mut assistant = Assistant::new(config, &provider, embedding_model, &usage).await?;
+            if let Some(path) = session.as_ref().filter(|path| path.exists()) {
+                assistant
+                    .agent
+                    .restore(utils::SessionSnapshot::load(path)?)?;
+            }
+
+            let result = prompt_with_approvals(&mut assistant.agent, prompt).await;
+            // whatever was remembered before an error is kept all the same
+            save_knowledge(config, &assistant.store)?;
+            let result = result?;
+            println!("\n{}", result.text);
+            let pending = assistant.knowledge.pending();
+            if !pending.is_empty() {
+                println!(
+                    "Dropped {} entries waiting for review, they can only be approved in `chat`",
+                    pending.len()
+                );
+            }
+            assistant
+                .memory
+                .record_turn(&assistant.summarizer, prompt, &result)
+                .await?;
+            print_sources(&result);
+
+            if let Some(path) = session {
+                assistant.agent.snapshot().save(path)?;
+            }
+            print_usage(&usage);
+        }
+        cli::Command::Chat => {
+            let assistant = Assistant::new(config, &provider, embedding_model, &usage).await?;
+            let mut agent = assistant.agent.with_event_handler(repl::print_event);
+            let result = repl::Repl {
+                knowledge: Box::new(assistant.store.clone().index()),
+                review: &assistant.knowledge,
+                memory: &assistant.memory,
+                summarizer: &assistant.summarizer,
+                history_file: &config.data.history_file,
+            }
+            .run(&mut agent)
+            .await;
+            // whatever was remembered before an error is kept all the same
+            save_knowledge(config, &assistant.store)?;
+            result?;
+            print_usage(&usage);
+        }
+        cli::Command::Eval { suite } => {
+            let suite = eval::Suite::load(suite)?;
+            let mut assistant = Assistant::new(config, &provider, embedding_model, &usage).await?;
+            let reports = suite.run(&mut assistant.agent).await;
+
+            let mut failed = 0;
+            for report in &reports {
+                if report.failures.is_empty() {
+                    println!("PASS {}", report.name);
+                    continue;
+                }
+                failed += 1;
+                println!("FAIL {}", report.name);
+                for failure in &report.failures {
+                    println!("  {failure}");
+                }
+            }
+            println!(
+                "\n{}/{} scenarios passed",
+                reports.len() - failed,
+                reports.len()
+            );
+            print_usage(&usage);
+            if failed > 0 {
+                anyhow::bail!("{failed} scenarios failed");
+            }
+        }
+        cli::Command::Memories { forget } => {
+            let memory = open_memory(config, embedding_model, utils::SessionHandle::new(), &usage)?;
+            let memories = memory.memories();
+            println!(
+                "Remembered {} facts about {}",
+                memories.len(),
+                config.data.user
+            );
+            for memory in &memories {
+                println!("  [{}] {}", memory.id, memory.fact);
+            }
+            match forget.as_deref() {
+                Some("all") => println!("Forgot {} memories", memory.forget_all()?),
+                Some(id) if memory.forget(id)? => println!("Forgot {id}"),
+                Some(id) => println!("No memory {id}"),
+                None => {}
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// the agent `ask`, `chat` and `eval` talk to, along with the stores it reads from
+struct Assistant<P: provider::Provider> {
+    agent: utils::MultiTurnAgent<P::Completion>,
+    store: Store<P::Embedding>,
+    knowledge: utils::KnowledgeBase<P::Embedding>,
+    memory: utils::MemoryStore<P::Embedding>,
+    summarizer: Agent<P::Completion>,
+}
+
+impl<P: provider::Provider> Assistant<P> {
+    async fn new(
+        config: &config::Config,
+        provider: &P,
+        embedding_model: P::Embedding,
+        usage: &utils::UsageTracker,
+    ) -> Result<Self> {
+        let vector_store = open_store(config, embedding_model.clone(), usage).await?;
+
+        // built-in units, plus the ones tagged in the knowledge base and an optional file
+        let mut units = tools::ConversionTable::builtin();
+        if let Some(path) = &config.data.units_file {
+            units = units.load(path)?;
+        }
+        // the store is a hash map, sorting keeps the same definition winning between runs
+        // when two documents define the same rate
+        let mut entries = vector_store.entries();
+        entries.sort_by(|(a, _, _), (b, _, _)| a.cmp(b));
+        for (_, document, _) in entries {
+            let texts = match document {
+                utils::KnowledgeDocument::Word(word) => word.definitions,
+                utils::KnowledgeDocument::Chunk(chunk) => vec![chunk.text],
+            };
+            for text in texts {
+                for definition in tools::UnitDefinition::extract_tags(&text) {
+                    units.define(definition);
+                }
+            }
+        }
+
+        let math = config.math.context();
+        // shared between the agent's tool and the session, which clears it with the history
+        let ledger = tools::Ledger::new(math);
+
+        // only the math tools closest to the prompt are offered, `calculate` stays static so
+        // there is always one to fall back on. they are embedded once, every profile gets an
+        // index of the ones it allows
+        let tool_embeddings = utils::embed_tracked(
+            embedding_model.clone(),
+            tools::math_toolset(math, |_| true).schemas()?,
+            usage,
+            config.provider.embedding_model(),
+        )
         .await?;
 
-    let vector_store = utils::VectorStore::new(
-        InMemoryVectorStore::from_documents(embeddings),
-        embedding_model.clone(),
-    );
+        // identical conversions are answered from here until the history is cleared. lookups
+        // aren't cached, the knowledge base can change under them
+        let tool_cache = tools::ToolCache::new();
+        // entries written by the agent are tagged with the session they come from
+        let session = utils::SessionHandle::new();
+        let mut knowledge = utils::KnowledgeBase::new(vector_store.clone(), session.clone());
+        if config.data.review_knowledge {
+            knowledge = knowledge.with_review();
+        }
 
-    let calculator_rag = client
-        .agent("gemini-2.0-flash")
-        .preamble("You are a helpful assistant. All algebraic operations must use the tools at your disposal. The \"lookup\" tool can not only be used to look up the definition of a word, but also to find any and all information regarding that word or concept. Use the \"lookup\" tool thoroughly to ensure you get the most accurate and relevant information. However, if you believe the information you are looking for is already in your context, do not use the \"lookup\" tool.")
-        .tool(tools::Add)
-        .tool(tools::Subtract)
-        .tool(tools::Multiply)
-        .tool(tools::Divide)
-        .tool(tools::Lookup::new(vector_store.clone().index()))
-        .dynamic_context(1, vector_store.index())
-        .additional_params(serde_json::to_value(GenerationConfig {
-            temperature: Some(0.0),
-            ..Default::default()
-        })?)
-        .build();
-
-    let mut agent = utils::MultiTurnAgent::new(calculator_rag);
-
-    let query = "Calculate 5 - 2 = ?. Describe the result to me.";
-    println!("Query #1: {}\n", query);
-    let result = agent.multi_turn_prompt(query).await?;
-    println!("\nResponse #1: {}\n\n", result);
-    agent.clear_history().await;
-
-    let query = "Calculate (2 + 3) / 10  = ?. Describe the result to me.";
-    println!("Query #2: {}\n", query);
-    let result = agent.multi_turn_prompt(query).await?;
-    println!("\nResponse #2: {}\n\n", result);
-    agent.clear_history().await;
-
-    let query = "What does \"glarb-glarb\" mean?";
-    println!("Query #3: {}\n", query);
-    let result = agent.multi_turn_prompt(query).await?;
-    println!("\nResponse #3: {}\n\n", result);
-    agent.clear_history().await;
-
-    // Then, once it has the definition, ask it to calculate the cost of a flurbo
-    let query = "Somebody gave me two flurbos yesterday, and i already had 12 before that, but then, I had to give 10% of it to the government this afternoon, how many flurbos do i have left? And how many USD would I have if I converted what I have right now?";
-    println!("Query #4: {}\n", query);
-    let result = agent.multi_turn_prompt(query).await?;
-    println!("\nResponse #4: {}", result);
-    agent.clear_history().await;
+        // what the user said in past sessions is retrieved next to the knowledge base, and
+        // every answered query may add to it
+        let memory = open_memory(config, embedding_model.clone(), session.clone(), usage)?;
+        let summarizer =
+            AgentBuilder::new(provider.completion_model(config.provider.completion_model())?)
+                .preamble(utils::MEMORY_PREAMBLE)
+                .additional_params(provider.additional_params(0.0)?)
+                .build();
 
+        // every profile has its own preamble, tools and temperature, over the same stores,
+        // ledger and cache
+        let retrieval = &config.retrieval;
+        let build = |profile: &config::ProfileConfig| -> Result<Agent<P::Completion>> {
+            let mut builder =
+                AgentBuilder::new(provider.completion_model(config.provider.completion_model())?)
+                    .preamble(&profile.preamble);
+            if profile.has_tool("convert") {
+                builder = builder.tool(
+                    tools::Middleware::new(tools::Convert::new(math, units.clone()))
+                        .with_cache(tool_cache.clone()),
+                );
+            }
+            if profile.has_tool("ledger") {
+                builder = builder.tool(ledger.clone());
+            }
+            if profile.has_tool("calculate") {
+                builder = builder.tool(tools::Calculate::new(math));
+            }
+            if profile.has_tool("remember") {
+                builder = builder.tool(tools::Remember::new(knowledge.clone()));
+            }
+            if profile.has_tool("lookup") {
+                builder = builder.tool(
+                    tools::Middleware::new(tools::Lookup::new(vector_store.clone().index()))
+                        .with_timeout(Duration::from_secs(10))
+                        .with_retry(tools::RetryPolicy {
+                            max_attempts: 3,
+                            backoff: Duration::from_millis(250),
+                        })
+                        .with_rate_limit(tools::RateLimit {
+                            max_calls: 20,
+                            per: Duration::from_secs(60),
+                        })
+                        .with_audit(|record| {
+                            tracing::info!(
+                                tool = record.tool,
+                                arguments = %record.arguments,
+                                ok = record.result.is_ok(),
+                                attempts = record.attempts,
+                                cached = record.cached,
+                                duration_ms = record.duration.as_millis() as u64,
+                                "tool audit"
+                            );
+                        }),
+                );
+            }
+
+            let math_tools = tools::math_toolset(math, |name| profile.has_tool(name));
+            let tool_store = utils::VectorStore::from_tools(
+                tool_embeddings
+                    .iter()
+                    .filter(|(tool, _)| profile.has_tool(&tool.name))
+                    .cloned()
+                    .collect(),
+                embedding_model.clone(),
+                config.provider.embedding_model(),
+            )
+            .with_usage_tracker(usage.clone());
+
+            Ok(builder
+                .dynamic_context(retrieval.context_top_k, vector_store.clone().index())
+                .dynamic_context(retrieval.memory_top_k, memory.index())
+                .dynamic_tools(retrieval.tool_top_k, tool_store.index(), math_tools)
+                .additional_params(provider.additional_params(profile.temperature)?)
+                .build())
+        };
+
+        let mut agent = utils::MultiTurnAgent::from_profile(
+            &config.profile,
+            build(&config.profiles[&config.profile])?,
+        );
+        for (name, profile) in &config.profiles {
+            if *name != config.profile {
+                agent = agent.with_profile(name, build(profile)?);
+            }
+        }
+
+        agent = agent.with_context_index(retrieval.context_top_k, vector_store.clone().index());
+        if retrieval.citations {
+            agent = agent.with_citations(retrieval.citation_threshold);
+        }
+        let agent = agent
+            .with_usage_tracker(usage.clone(), config.provider.completion_model())
+            .with_ledger(ledger)
+            .with_tool_cache(tool_cache)
+            .with_session_handle(session)
+            // reading the ledger is harmless, changing it (or the knowledge base) is up to the
+            // user
+            .with_approval("ledger", |args| {
+                !matches!(args["action"].as_str(), Some("balance" | "history"))
+            })
+            .with_approval("remember", |_| true);
+
+        Ok(Self {
+            agent,
+            store: vector_store,
+            knowledge,
+            memory,
+            summarizer,
+        })
+    }
+}
+
+/// the store saved by `ingest`, or just the glossary below if nothing was ingested yet.
+/// everything embedded through it, queries included, is tracked
+async fn open_store<E: EmbeddingModel>(
+    config: &config::Config,
+    embedding_model: E,
+    usage: &utils::UsageTracker,
+) -> Result<Store<E>> {
+    let path = &config.data.store;
+    let store = if path.exists() {
+        utils::VectorStore::load(path, embedding_model, config.provider.embedding_model())?
+    } else {
+        tracing::info!(store = %path.display(), "no vector store yet, embedding the glossary");
+        let embeddings = utils::embed_tracked(
+            embedding_model.clone(),
+            glossary(),
+            usage,
+            config.provider.embedding_model(),
+        )
+        .await?;
+        utils::VectorStore::new(
+            InMemoryVectorStore::from_documents(embeddings),
+            embedding_model,
+            config.provider.embedding_model(),
+        )
+    };
+    Ok(store.with_usage_tracker(usage.clone()))
+}
+
+/// writes the store back if the `remember` tool added to it, so the next run sees it
+fn save_knowledge<E: EmbeddingModel>(config: &config::Config, store: &Store<E>) -> Result<()> {
+    if store.is_modified() {
+        store.save(&config.data.store)?;
+        println!("Saved the vector store to {}", config.data.store.display());
+    }
     Ok(())
 }
+
+fn glossary() -> Vec<utils::KnowledgeDocument> {
+    vec![
+        utils::WordDefinition {
+            id: "doc0".to_string(),
+            word: "flurbo".to_string(),
+            definitions: vec![
+                "1. *flurbo* (name): A flurbo is a green alien that lives on cold planets.".to_string(),
+                "2. *flurbo* (name): A fictional digital currency that originated in the animated series Rick and Morty. Each flurbo is worth 10 USD [convert: 1 flurbo = 10 USD], and you can have and/or give away a fraction of a flurbo (0.3 flurbos, for example).".to_string()
+            ],
+            ..Default::default()
+        },
+        utils::WordDefinition {
+            id: "doc1".to_string(),
+            word: "glarb-glarb".to_string(),
+            definitions: vec![
+                "1. *glarb-glarb* (noun): A glarb-glarb is a ancient tool used by the ancestors of the inhabitants of planet Jiro to farm the land.".to_string(),
+                "2. *glarb-glarb* (noun): A fictional creature found in the distant, swampy marshlands of the planet Glibbo in the Andromeda galaxy.".to_string()
+            ],
+            ..Default::default()
+        },
+        utils::WordDefinition {
+            id: "doc2".to_string(),
+            word: "linglingdong".to_string(),
+            definitions: vec![
+                "1. *linglingdong* (noun): A term used by inhabitants of the far side of the moon to describe humans.".to_string(),
+                "2. *linglingdong* (noun): A rare, mystical instrument crafted by the ancient monks of the Nebulon Mountain Ranges on the planet Quarm.".to_string()
+            ],
+            ..Default::default()
+        },
+    ]
+    .into_iter()
+    .map(utils::KnowledgeDocument::Word)
+    .collect()
+}
+
+/// the memories of `data.user`, kept in `data.memory_dir`
+fn open_memory<E: EmbeddingModel>(
+    config: &config::Config,
+    embedding_model: E,
+    session: utils::SessionHandle,
+    usage: &utils::UsageTracker,
+) -> Result<utils::MemoryStore<E>> {
+    Ok(utils::MemoryStore::open(
+        &config.data.memory_dir,
+        &config.data.user,
+        embedding_model,
+        config.provider.embedding_model(),
+        session,
+    )?
+    .with_usage_tracker(usage.clone(), config.provider.completion_model()))
+}
+
+/// asks on stdin about every call the agent pauses on: "y" approves it, "n" (optionally
+/// followed by a reason) denies it, and a JSON object replaces its arguments. anything
+/// else is asked again
+async fn prompt_with_approvals<M>(
+    agent: &mut utils::MultiTurnAgent<M>,
+    query: &str,
+) -> Result<utils::TurnOutcome>
+where
+    M: rig::completion::CompletionModel,
+    M::Response: utils::ReportsUsage,
+{
+    let mut result = agent.multi_turn_prompt(query).await?;
+    while let Some(call) = result.pending_approval.take() {
+        println!(
+            "\nApprove `{}` with {}? [y / n <reason> / edited JSON arguments]",
+            call.name, call.arguments
+        );
+        let decision = loop {
+            let mut answer = String::new();
+            // stdin is closed, nobody is left to approve it
+            if std::io::stdin().read_line(&mut answer)? == 0 {
+                break utils::ApprovalDecision::Deny { reason: None };
+            }
+            match answer.parse() {
+                Ok(decision) => break decision,
+                Err(err) => println!("{err}"),
+            }
+        };
+        result = agent.resume(decision).await?;
+    }
+    Ok(result)
+}
+
+fn print_sources(answer: &utils::TurnOutcome) {
+    if let Some(usage) = answer.usage {
+        println!(
+            "Usage: {} tokens over {} turns",
+            usage.total_tokens(),
+            answer.turns
+        );
+    }
+    let ids = answer.provenance.document_ids();
+    if !ids.is_empty() {
+        println!("Sources: {}", ids.join(", "));
+    }
+    for citation in &answer.provenance.citations {
+        println!(
+            "  \"{}\" [{}]",
+            citation.sentence,
+            citation.documents.join(", ")
+        );
+    }
+}
+
+fn print_usage(usage: &utils::UsageTracker) {
+    let total = usage.total();
+    println!(
+        "\nSession usage: {} prompt, {} completion and {} embedding tokens (${:.6})",
+        total.prompt_tokens,
+        total.completion_tokens,
+        total.embedding_tokens,
+        usage.cost()
+    );
+    let turns: Vec<String> = usage
+        .turns()
+        .iter()
+        .map(|turn| turn.total_tokens().to_string())
+        .collect();
+    if !turns.is_empty() {
+        println!("Tokens per prompt: {}", turns.join(", "));
+    }
+}
@@ -0,0 +1,134 @@
+use rig::{
+    completion::CompletionModel,
+    embeddings::EmbeddingModel,
+    providers::{
+        gemini::{self, completion::gemini_api_types::GenerationConfig},
+        openai,
+    },
+};
+use serde_json::{Value, json};
+
+use crate::{
+    config::ProviderConfig,
+    utils::{HashingEmbeddingModel, ReportsUsage},
+};
+
+/// what the app needs from a completion and embedding provider, so everything past
+/// `main` is written once for all of them
+pub trait Provider {
+    type Completion: CompletionModel<Response: ReportsUsage> + 'static;
+    type Embedding: EmbeddingModel + 'static;
+
+    /// fails if the provider couldn't be set up, e.g. without an API key, which only
+    /// matters to the commands talking to the model
+    fn completion_model(&self, name: &str) -> anyhow::Result<Self::Completion>;
+
+    fn embedding_model(&self, name: &str, ndims: usize) -> Self::Embedding;
+
+    /// the sampling settings in the shape the provider expects them in
+    /// `AgentBuilder::additional_params`
+    fn additional_params(&self, temperature: f64) -> anyhow::Result<Value>;
+}
+
+pub struct Gemini {
+    client: gemini::Client,
+}
+
+impl Gemini {
+    pub fn new(config: &ProviderConfig) -> anyhow::Result<Self> {
+        Ok(Self {
+            client: gemini::Client::new(&api_key(config)?),
+        })
+    }
+}
+
+impl Provider for Gemini {
+    type Completion = gemini::completion::CompletionModel;
+    type Embedding = gemini::embedding::EmbeddingModel;
+
+    fn completion_model(&self, name: &str) -> anyhow::Result<Self::Completion> {
+        Ok(self.client.completion_model(name))
+    }
+
+    fn embedding_model(&self, name: &str, ndims: usize) -> Self::Embedding {
+        self.client.embedding_model_with_ndims(name, ndims)
+    }
+
+    fn additional_params(&self, temperature: f64) -> anyhow::Result<Value> {
+        Ok(serde_json::to_value(GenerationConfig {
+            temperature: Some(temperature),
+            ..Default::default()
+        })?)
+    }
+}
+
+/// anything speaking the OpenAI HTTP API: OpenAI itself, vLLM, LM Studio, llama.cpp's
+/// server, or Ollama through its `/v1` endpoints
+pub struct OpenAiCompatible {
+    client: openai::Client,
+}
+
+impl OpenAiCompatible {
+    pub fn new(config: &ProviderConfig) -> anyhow::Result<Self> {
+        let base_url = config
+            .base_url()
+            .ok_or_else(|| anyhow::anyhow!("Set `provider.base_url` for {:?}", config.kind))?;
+        Ok(Self {
+            client: openai::Client::from_url(&api_key(config)?, base_url),
+        })
+    }
+}
+
+impl Provider for OpenAiCompatible {
+    type Completion = openai::CompletionModel;
+    type Embedding = openai::EmbeddingModel;
+
+    fn completion_model(&self, name: &str) -> anyhow::Result<Self::Completion> {
+        Ok(self.client.completion_model(name))
+    }
+
+    fn embedding_model(&self, name: &str, ndims: usize) -> Self::Embedding {
+        self.client.embedding_model_with_ndims(name, ndims)
+    }
+
+    fn additional_params(&self, temperature: f64) -> anyhow::Result<Value> {
+        Ok(json!({ "temperature": temperature }))
+    }
+}
+
+/// completions from `P`, embeddings from `HashingEmbeddingModel`, so ingesting and
+/// searching never leave the machine. `P` failing to set up (no API key, no base URL)
+/// is only an error once a completion model is asked for
+pub struct LocalEmbeddings<P>(pub anyhow::Result<P>);
+
+impl<P: Provider> LocalEmbeddings<P> {
+    fn completions(&self) -> anyhow::Result<&P> {
+        self.0.as_ref().map_err(|err| anyhow::anyhow!("{err:#}"))
+    }
+}
+
+impl<P: Provider> Provider for LocalEmbeddings<P> {
+    type Completion = P::Completion;
+    type Embedding = HashingEmbeddingModel;
+
+    fn completion_model(&self, name: &str) -> anyhow::Result<Self::Completion> {
+        self.completions()?.completion_model(name)
+    }
+
+    fn embedding_model(&self, _name: &str, ndims: usize) -> Self::Embedding {
+        HashingEmbeddingModel::new(ndims)
+    }
+
+    fn additional_params(&self, temperature: f64) -> anyhow::Result<Value> {
+        self.completions()?.additional_params(temperature)
+    }
+}
+
+/// the key in `api_key_env`. a local Ollama doesn't need one, but the client still sends
+/// something
+fn api_key(config: &ProviderConfig) -> anyhow::Result<String> {
+    let Some(name) = config.api_key_env() else {
+        return Ok("ollama".to_string());
+    };
+    std::env::var(name).map_err(|_| anyhow::anyhow!("Set {name} to the API key of the provider"))
+}
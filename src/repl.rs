@@ -0,0 +1,290 @@
+use std::path::Path;
+
+use rig::{
+    agent::Agent,
+    completion::CompletionModel,
+    embeddings::EmbeddingModel,
+    message::{AssistantContent, Message, ToolResultContent, UserContent},
+    vector_store::VectorStoreIndexDyn,
+};
+use rustyline::{DefaultEditor, error::ReadlineError};
+
+use crate::utils::{
+    self, ApprovalDecision, KnowledgeBase, MemoryStore, MultiTurnAgent, ReportsUsage, TurnEvent,
+    TurnOutcome,
+};
+
+const HELP: &str = "\
+/clear           start a new session
+/history         show the conversation so far
+/profile [name]  show the profiles, or start a new session in another one
+/tools           list the tools the agent can call
+/save <path>     save the session to a file
+/load <path>     continue a session saved with /save
+/lookup <term>   search the knowledge base directly
+/review          list the entries `remember` wrote that wait for approval
+/approve <id>    make an entry waiting for review searchable
+/reject <id>     drop an entry waiting for review
+/trace           show everything that happened while answering the last question
+/help            show this message
+/exit            quit (or Ctrl-D)";
+
+/// prints the model's texts and tool calls as the turn goes, meant for
+/// `MultiTurnAgent::with_event_handler`
+pub fn print_event(event: &TurnEvent) {
+    match event {
+        TurnEvent::Text(text) => println!("{text}"),
+        TurnEvent::ToolCall { name, arguments } => println!("  -> {name} {arguments}"),
+        TurnEvent::ToolResult(invocation) => match (&invocation.result, &invocation.error) {
+            (Some(result), _) => println!("  <- {} {}", invocation.name, truncate(result, 200)),
+            (None, Some(error)) => println!("  !! {} {error}", invocation.name),
+            (None, None) => {}
+        },
+        TurnEvent::ApprovalNeeded(call) => {
+            println!("  ?? {} {} needs your approval", call.name, call.arguments);
+        }
+    }
+}
+
+/// everything the REPL works with besides the agent itself
+pub struct Repl<'a, M: CompletionModel, E: EmbeddingModel> {
+    pub knowledge: Box<dyn VectorStoreIndexDyn + Send + Sync>,
+    /// where `/review`, `/approve` and `/reject` find what `remember` wrote
+    pub review: &'a KnowledgeBase<E>,
+    pub memory: &'a MemoryStore<E>,
+    pub summarizer: &'a Agent<M>,
+    /// where the readline history is kept between runs
+    pub history_file: &'a Path,
+}
+
+enum Flow {
+    Continue,
+    Exit,
+}
+
+impl<M, E> Repl<'_, M, E>
+where
+    M: CompletionModel,
+    M::Response: ReportsUsage,
+    E: EmbeddingModel,
+{
+    /// reads questions and slash commands until `/exit` or Ctrl-D. the agent should print
+    /// its turns through `print_event`, answers aren't printed again here
+    pub async fn run(self, agent: &mut MultiTurnAgent<M>) -> anyhow::Result<()> {
+        let mut editor = DefaultEditor::new()?;
+        // there is no history file on the first run
+        let _ = editor.load_history(self.history_file);
+        let mut last = None;
+
+        println!("Ask anything, or type /help to see the commands.");
+        loop {
+            let line = match editor.readline(">> ") {
+                Ok(line) => line,
+                // Ctrl-C only drops the current line
+                Err(ReadlineError::Interrupted) => continue,
+                Err(ReadlineError::Eof) => break,
+                Err(err) => return Err(err.into()),
+            };
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            editor.add_history_entry(line)?;
+
+            let flow = match line.strip_prefix('/') {
+                Some(command) => self.command(agent, command, &last).await,
+                None => self.ask(agent, &mut editor, line).await.map(|outcome| {
+                    last = Some(outcome);
+                    Flow::Continue
+                }),
+            };
+            match flow {
+                Ok(Flow::Continue) => {}
+                Ok(Flow::Exit) => break,
+                // a failed turn or command doesn't end the session
+                Err(err) => println!("Error: {err}"),
+            }
+        }
+
+        editor.save_history(self.history_file)?;
+        Ok(())
+    }
+
+    async fn ask(
+        &self,
+        agent: &mut MultiTurnAgent<M>,
+        editor: &mut DefaultEditor,
+        question: &str,
+    ) -> anyhow::Result<TurnOutcome> {
+        let mut outcome = agent.multi_turn_prompt(question).await?;
+        while let Some(call) = outcome.pending_approval.take() {
+            let prompt = format!(
+                "Approve `{}`? [y / n <reason> / edited JSON arguments] ",
+                call.name
+            );
+            let decision = loop {
+                match editor.readline(&prompt) {
+                    Ok(answer) => match answer.parse() {
+                        Ok(decision) => break decision,
+                        Err(err) => println!("{err}"),
+                    },
+                    Err(ReadlineError::Interrupted | ReadlineError::Eof) => {
+                        break ApprovalDecision::Deny { reason: None };
+                    }
+                    Err(err) => return Err(err.into()),
+                }
+            };
+            outcome = agent.resume(decision).await?;
+        }
+
+        let ids = outcome.provenance.document_ids();
+        if !ids.is_empty() {
+            println!("  (sources: {})", ids.join(", "));
+        }
+        self.memory
+            .record_turn(self.summarizer, question, &outcome)
+            .await?;
+        Ok(outcome)
+    }
+
+    async fn command(
+        &self,
+        agent: &mut MultiTurnAgent<M>,
+        command: &str,
+        last: &Option<TurnOutcome>,
+    ) -> anyhow::Result<Flow> {
+        let (name, argument) = match command.split_once(char::is_whitespace) {
+            Some((name, argument)) => (name, argument.trim()),
+            None => (command, ""),
+        };
+
+        match (name, argument) {
+            ("clear", _) => {
+                agent.clear_history().await;
+                println!("Started session {}", agent.session_id());
+            }
+            ("history", _) => {
+                for message in agent.chat_history() {
+                    for line in describe(message) {
+                        println!("{line}");
+                    }
+                }
+            }
+            ("profile", "") => {
+                for profile in agent.profiles() {
+                    let marker = if profile == agent.profile() { "*" } else { " " };
+                    println!("{marker} {profile}");
+                }
+            }
+            ("profile", profile) => {
+                agent.set_profile(profile).await?;
+                println!(
+                    "Started session {} as {}",
+                    agent.session_id(),
+                    agent.profile()
+                );
+            }
+            ("tools", _) => {
+                for tool in agent.tool_definitions().await {
+                    println!("{}: {}", tool.name, truncate(&tool.description, 100));
+                }
+            }
+            ("save", path) if !path.is_empty() => {
+                agent.snapshot().save(path)?;
+                println!("Saved session {} to {path}", agent.session_id());
+            }
+            ("load", path) if !path.is_empty() => {
+                agent.restore(utils::SessionSnapshot::load(path)?)?;
+                println!(
+                    "Continuing session {} ({} messages)",
+                    agent.session_id(),
+                    agent.chat_history().len()
+                );
+            }
+            ("lookup", term) if !term.is_empty() => {
+                for (score, id, document) in self.knowledge.top_n(term, 3).await? {
+                    println!("[{id}] {score:.3} {}", truncate(&document.to_string(), 200));
+                }
+            }
+            ("review", _) => {
+                let pending = self.review.pending();
+                if pending.is_empty() {
+                    println!("Nothing waiting for review");
+                }
+                for entry in pending {
+                    println!(
+                        "[{}] {}: {}",
+                        entry.id,
+                        entry.word,
+                        entry.definitions.join(" ")
+                    );
+                }
+            }
+            ("approve", id) if !id.is_empty() => {
+                if self.review.approve(id) {
+                    println!("Added {id} to the knowledge base");
+                } else {
+                    println!("Nothing waiting for review under {id}, see /review");
+                }
+            }
+            ("reject", id) if !id.is_empty() => {
+                if self.review.reject(id) {
+                    println!("Dropped {id}");
+                } else {
+                    println!("Nothing waiting for review under {id}, see /review");
+                }
+            }
+            ("trace", _) => match last {
+                Some(outcome) => println!("{}", serde_json::to_string_pretty(outcome)?),
+                None => println!("Nothing answered yet"),
+            },
+            ("help", _) => println!("{HELP}"),
+            ("exit" | "quit", _) => return Ok(Flow::Exit),
+            ("save" | "load" | "lookup" | "approve" | "reject", _) => {
+                println!("/{name} needs an argument, see /help")
+            }
+            _ => println!("Unknown command /{name}, see /help"),
+        }
+        Ok(Flow::Continue)
+    }
+}
+
+/// one line per piece of content in `message`
+fn describe(message: &Message) -> Vec<String> {
+    match message {
+        Message::User { content } => content
+            .iter()
+            .map(|content| match content {
+                UserContent::Text(text) => format!("user: {}", text.text),
+                UserContent::ToolResult(result) => {
+                    let texts: Vec<&str> = result
+                        .content
+                        .iter()
+                        .filter_map(|content| match content {
+                            ToolResultContent::Text(text) => Some(text.text.as_str()),
+                            _ => None,
+                        })
+                        .collect();
+                    format!("  <- {}", truncate(&texts.join(" "), 200))
+                }
+                _ => "user: [attachment]".to_string(),
+            })
+            .collect(),
+        Message::Assistant { content } => content
+            .iter()
+            .map(|content| match content {
+                AssistantContent::Text(text) => format!("assistant: {}", text.text),
+                AssistantContent::ToolCall(call) => {
+                    format!("  -> {} {}", call.function.name, call.function.arguments)
+                }
+            })
+            .collect(),
+    }
+}
+
+pub(crate) fn truncate(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text.to_string(),
+    }
+}
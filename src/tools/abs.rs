@@ -0,0 +1,61 @@
+use rig::{
+    completion::ToolDefinition,
+    tool::{Tool, ToolEmbedding},
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    InitError, MathError, UnaryArgs, definition,
+    math::{MathContext, MathOutput},
+};
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct Abs {
+    context: MathContext,
+}
+
+impl Abs {
+    pub fn new(context: MathContext) -> Self {
+        Self { context }
+    }
+}
+
+impl Tool for Abs {
+    const NAME: &'static str = "abs";
+
+    type Error = MathError;
+    type Args = UnaryArgs;
+    type Output = MathOutput;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        definition::<Self::Args>(
+            Self::NAME,
+            "Compute the Absolute Value of x (i.e.: |x|), its distance from zero. Useful for differences where only the magnitude matters.",
+        )
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        tracing::info!(tool = "abs", x = %args.x, "performing operation");
+        let output = self.context.output(args.operand(&self.context)?.abs());
+        tracing::info!(tool = "abs", result = %output.result, "operation result");
+        Ok(output)
+    }
+}
+
+impl ToolEmbedding for Abs {
+    type InitError = InitError;
+    type Context = MathContext;
+    type State = ();
+
+    fn init(_state: Self::State, context: Self::Context) -> Result<Self, Self::InitError> {
+        Ok(Abs::new(context))
+    }
+
+    fn context(&self) -> Self::Context {
+        self.context
+    }
+
+    fn embedding_docs(&self) -> Vec<String> {
+        vec!["Compute the Absolute Value of x (i.e.: |x|), its distance from zero. Useful for differences where only the magnitude matters.".into()]
+    }
+}
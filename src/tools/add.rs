@@ -3,61 +3,56 @@ use rig::{
     tool::{Tool, ToolEmbedding},
 };
 use serde::{Deserialize, Serialize};
-use serde_json::json;
 
-use super::{InitError, MathError, OperationArgs};
+use super::{
+    InitError, MathError, OperationArgs, definition,
+    math::{BinaryOp, MathContext, MathOutput},
+};
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct Add {
+    context: MathContext,
+}
 
-#[derive(Deserialize, Serialize)]
-pub struct Add;
+impl Add {
+    pub fn new(context: MathContext) -> Self {
+        Self { context }
+    }
+}
 
 impl Tool for Add {
     const NAME: &'static str = "add";
 
     type Error = MathError;
     type Args = OperationArgs;
-    type Output = f64;
+    type Output = MathOutput;
 
     async fn definition(&self, _prompt: String) -> ToolDefinition {
-        serde_json::from_value(json!({
-            "name": "add",
-            "description": "Add x and y together",
-            "parameters": {
-                "type": "object",
-                "properties": {
-                    "x": {
-                        "type": "number",
-                        "description": "The first number to add"
-                    },
-                    "y": {
-                        "type": "number",
-                        "description": "The second number to add"
-                    }
-                }
-            }
-        }))
-        .expect("Tool Definition")
+        definition::<Self::Args>(Self::NAME, "Add x and y together")
     }
 
     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
-        println!("[add] performing operation \"{}\" + \"{}\"", args.x, args.y);
-        let result = ((args.x + args.y) * 100.0).round() / 100.0;
-        println!("[add] result: {}", result);
-        Ok(result)
+        tracing::info!(tool = "add", x = %args.x, y = %args.y, "performing operation");
+        let output = args.evaluate(&self.context, BinaryOp::Add)?;
+        tracing::info!(tool = "add", result = %output.result, "operation result");
+        Ok(output)
     }
 }
 
 impl ToolEmbedding for Add {
     type InitError = InitError;
-    type Context = ();
+    type Context = MathContext;
     type State = ();
 
-    fn init(_state: Self::State, _context: Self::Context) -> Result<Self, Self::InitError> {
-        Ok(Add)
+    fn init(_state: Self::State, context: Self::Context) -> Result<Self, Self::InitError> {
+        Ok(Add::new(context))
     }
 
     fn embedding_docs(&self) -> Vec<String> {
         vec!["Add x and y together".into()]
     }
 
-    fn context(&self) -> Self::Context {}
+    fn context(&self) -> Self::Context {
+        self.context
+    }
 }
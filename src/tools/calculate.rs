@@ -0,0 +1,594 @@
+use rig::{
+    completion::ToolDefinition,
+    tool::{Tool, ToolEmbedding},
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    InitError, MathError, definition,
+    math::{BinaryOp, MathContext, Number, RoundingMode},
+};
+
+/// expressions are plain arithmetic, these only keep a hostile input from blowing the
+/// stack or hogging the tool
+const MAX_LENGTH: usize = 1000;
+const MAX_DEPTH: usize = 64;
+
+#[derive(Deserialize, JsonSchema)]
+pub struct Args {
+    /// The expression to evaluate, e.g. "(2 + 3) / 10" or "10% of 14"
+    expression: String,
+}
+
+#[derive(Serialize)]
+pub struct CalculateOutput {
+    /// the exact result, meant for further calculations
+    pub result: Number,
+    /// the result rounded for presentation
+    pub display: String,
+    /// every intermediate operation, in evaluation order
+    pub steps: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct Calculate {
+    context: MathContext,
+}
+
+impl Calculate {
+    pub fn new(context: MathContext) -> Self {
+        Self { context }
+    }
+}
+
+impl Tool for Calculate {
+    const NAME: &'static str = "calculate";
+
+    type Error = MathError;
+    type Args = Args;
+    type Output = CalculateOutput;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        definition::<Self::Args>(
+            Self::NAME,
+            "Evaluate a whole arithmetic expression at once and return its result along with every intermediate step. Supports + - * / ^ (or **), `mod`, parentheses, unary minus, percentages (`10%` is 0.1, `10% of 14` is 1.4), the constants `pi` and `e` and the functions sqrt, cbrt, root(x, n), pow(x, y), abs, round(x[, places]), floor, ceil, min(...), max(...), exp, ln, log(x[, base]), log2, sin, cos, tan, asin, acos and atan (radians).",
+        )
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        tracing::info!(tool = "calculate", expression = %args.expression, "evaluating expression");
+        let (result, steps) = evaluate(&self.context, &args.expression)?;
+        tracing::info!(tool = "calculate", %result, steps = steps.len(), "expression result");
+        Ok(CalculateOutput {
+            result,
+            display: self.context.display(result),
+            steps,
+        })
+    }
+}
+
+impl ToolEmbedding for Calculate {
+    type InitError = InitError;
+    type Context = MathContext;
+    type State = ();
+
+    fn init(_state: Self::State, context: Self::Context) -> Result<Self, Self::InitError> {
+        Ok(Calculate::new(context))
+    }
+
+    fn context(&self) -> Self::Context {
+        self.context
+    }
+
+    fn embedding_docs(&self) -> Vec<String> {
+        vec![
+            "Evaluate a whole arithmetic expression, with parentheses, powers, percentages and functions".into(),
+            "Compute a multi-step calculation like (2 + 3) / 10 in a single call".into(),
+        ]
+    }
+}
+
+/// returns the result and the trace of every operation performed to get there
+pub fn evaluate(
+    context: &MathContext,
+    expression: &str,
+) -> Result<(Number, Vec<String>), MathError> {
+    if expression.len() > MAX_LENGTH {
+        return Err(invalid(format!(
+            "is too long ({} bytes, at most {MAX_LENGTH} are allowed)",
+            expression.len()
+        )));
+    }
+
+    let mut evaluator = Evaluator {
+        context,
+        tokens: tokenize(expression)?,
+        pos: 0,
+        depth: 0,
+        steps: Vec::new(),
+    };
+
+    let result = evaluator.expression()?;
+    if let Some((position, token)) = evaluator.tokens.get(evaluator.pos) {
+        return Err(invalid(format!(
+            "unexpected `{token}` at position {position}"
+        )));
+    }
+
+    Ok((result, evaluator.steps))
+}
+
+fn invalid(reason: String) -> MathError {
+    MathError::InvalidArgument {
+        name: "expression".to_string(),
+        reason,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(String),
+    Ident(String),
+    Symbol(char),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Number(text) | Self::Ident(text) => f.write_str(text),
+            Self::Symbol(symbol) => write!(f, "{symbol}"),
+        }
+    }
+}
+
+/// tokens along with their (char) position in the expression
+fn tokenize(expression: &str) -> Result<Vec<(usize, Token)>, MathError> {
+    let chars: Vec<char> = expression.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        if c.is_ascii_digit() || c == '.' {
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            // exponent, only when followed by digits. there is no implicit multiplication,
+            // so `2e` is the number 2 followed by an unexpected `e`
+            if i < chars.len() && matches!(chars[i], 'e' | 'E') {
+                let mut j = i + 1;
+                if j < chars.len() && matches!(chars[j], '+' | '-') {
+                    j += 1;
+                }
+                if j < chars.len() && chars[j].is_ascii_digit() {
+                    i = j;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            tokens.push((start, Token::Number(chars[start..i].iter().collect())));
+            continue;
+        }
+
+        if c.is_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let ident: String = chars[start..i].iter().collect();
+            tokens.push((start, Token::Ident(ident.to_lowercase())));
+            continue;
+        }
+
+        let symbol = match c {
+            '×' => '*',
+            '÷' => '/',
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                i += 1;
+                '^'
+            }
+            '+' | '-' | '*' | '/' | '^' | '%' | '(' | ')' | ',' => c,
+            _ => return Err(invalid(format!("unexpected `{c}` at position {start}"))),
+        };
+        tokens.push((start, Token::Symbol(symbol)));
+        i += 1;
+    }
+
+    Ok(tokens)
+}
+
+struct Evaluator<'a> {
+    context: &'a MathContext,
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    depth: usize,
+    steps: Vec<String>,
+}
+
+impl Evaluator<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, token)| token)
+    }
+
+    fn eat_symbol(&mut self, symbol: char) -> bool {
+        if self.peek() == Some(&Token::Symbol(symbol)) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn eat_ident(&mut self, ident: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Ident(name)) if name == ident) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn unexpected(&self) -> MathError {
+        match self.tokens.get(self.pos) {
+            Some((position, token)) => {
+                invalid(format!("unexpected `{token}` at position {position}"))
+            }
+            None => invalid("ended unexpectedly".to_string()),
+        }
+    }
+
+    fn descend(&mut self) -> Result<(), MathError> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(invalid(format!(
+                "is nested too deeply (at most {MAX_DEPTH} levels are allowed)"
+            )));
+        }
+        Ok(())
+    }
+
+    fn binary(&mut self, lhs: Number, op: BinaryOp, rhs: Number) -> Result<Number, MathError> {
+        let result = lhs.apply(op, rhs)?;
+        self.steps.push(format!("{lhs} {op} {rhs} = {result}"));
+        Ok(result)
+    }
+
+    /// expression := term (("+" | "-") term)*
+    fn expression(&mut self) -> Result<Number, MathError> {
+        let mut lhs = self.term()?;
+        loop {
+            let op = if self.eat_symbol('+') {
+                BinaryOp::Add
+            } else if self.eat_symbol('-') {
+                BinaryOp::Subtract
+            } else {
+                return Ok(lhs);
+            };
+            let rhs = self.term()?;
+            lhs = self.binary(lhs, op, rhs)?;
+        }
+    }
+
+    /// term := unary (("*" | "/" | "mod" | "of") unary)*
+    fn term(&mut self) -> Result<Number, MathError> {
+        let mut lhs = self.unary()?;
+        loop {
+            let op = if self.eat_symbol('*') || self.eat_ident("of") {
+                BinaryOp::Multiply
+            } else if self.eat_symbol('/') {
+                BinaryOp::Divide
+            } else if self.eat_ident("mod") {
+                BinaryOp::Remainder
+            } else {
+                return Ok(lhs);
+            };
+            let rhs = self.unary()?;
+            lhs = self.binary(lhs, op, rhs)?;
+        }
+    }
+
+    /// unary := ("-" | "+") unary | power
+    fn unary(&mut self) -> Result<Number, MathError> {
+        if self.eat_symbol('-') {
+            self.descend()?;
+            let value = self.unary()?.neg();
+            self.depth -= 1;
+            return Ok(value);
+        }
+        if self.eat_symbol('+') {
+            self.descend()?;
+            let value = self.unary()?;
+            self.depth -= 1;
+            return Ok(value);
+        }
+        self.power()
+    }
+
+    /// power := postfix ("^" unary)?, right associative and binding tighter than a
+    /// leading minus, so `-2^2` is -4 and `2^-1` is 0.5
+    fn power(&mut self) -> Result<Number, MathError> {
+        let base = self.postfix()?;
+        if !self.eat_symbol('^') {
+            return Ok(base);
+        }
+        self.descend()?;
+        let exponent = self.unary()?;
+        self.depth -= 1;
+        self.binary(base, BinaryOp::Power, exponent)
+    }
+
+    /// postfix := primary "%"*
+    fn postfix(&mut self) -> Result<Number, MathError> {
+        let mut value = self.primary()?;
+        while self.eat_symbol('%') {
+            let result = value.apply(BinaryOp::Divide, self.context.number("%", 100.0)?)?;
+            self.steps.push(format!("{value}% = {result}"));
+            value = result;
+        }
+        Ok(value)
+    }
+
+    /// primary := number | constant | function "(" arguments ")" | "(" expression ")"
+    fn primary(&mut self) -> Result<Number, MathError> {
+        let Some((position, token)) = self.tokens.get(self.pos).cloned() else {
+            return Err(self.unexpected());
+        };
+
+        match token {
+            Token::Number(text) => {
+                self.pos += 1;
+                self.context.parse("expression", &text)
+            }
+            Token::Symbol('(') => {
+                self.pos += 1;
+                self.descend()?;
+                let value = self.expression()?;
+                self.depth -= 1;
+                if !self.eat_symbol(')') {
+                    return Err(self.unexpected());
+                }
+                Ok(value)
+            }
+            Token::Ident(name) => {
+                self.pos += 1;
+                if !self.eat_symbol('(') {
+                    return match name.as_str() {
+                        "pi" => self.context.computed(std::f64::consts::PI, || name.clone()),
+                        "e" => self.context.computed(std::f64::consts::E, || name.clone()),
+                        _ => Err(invalid(format!(
+                            "unknown constant `{name}` at position {position}"
+                        ))),
+                    };
+                }
+
+                self.descend()?;
+                let mut arguments = vec![self.expression()?];
+                while self.eat_symbol(',') {
+                    arguments.push(self.expression()?);
+                }
+                self.depth -= 1;
+                if !self.eat_symbol(')') {
+                    return Err(self.unexpected());
+                }
+
+                let result = self.function(&name, position, &arguments)?;
+                let arguments: Vec<String> = arguments.iter().map(Number::to_string).collect();
+                self.steps
+                    .push(format!("{name}({}) = {result}", arguments.join(", ")));
+                Ok(result)
+            }
+            Token::Symbol(_) => Err(self.unexpected()),
+        }
+    }
+
+    fn function(
+        &self,
+        name: &str,
+        position: usize,
+        arguments: &[Number],
+    ) -> Result<Number, MathError> {
+        let arity = |expected: &[usize]| -> Result<(), MathError> {
+            if expected.contains(&arguments.len()) {
+                return Ok(());
+            }
+            let expected: Vec<String> = expected.iter().map(usize::to_string).collect();
+            Err(invalid(format!(
+                "`{name}` at position {position} takes {} argument(s), got {}",
+                expected.join(" or "),
+                arguments.len()
+            )))
+        };
+
+        match name {
+            "abs" | "floor" | "ceil" | "sqrt" | "cbrt" | "exp" | "ln" | "log2" | "sin" | "cos"
+            | "tan" | "asin" | "acos" | "atan" => arity(&[1])?,
+            "round" | "log" => arity(&[1, 2])?,
+            "pow" | "root" => arity(&[2])?,
+            "min" | "max" if !arguments.is_empty() => {}
+            "min" | "max" => {
+                return Err(invalid(format!(
+                    "`{name}` at position {position} needs at least one argument"
+                )));
+            }
+            _ => {
+                return Err(invalid(format!(
+                    "unknown function `{name}` at position {position}"
+                )));
+            }
+        }
+
+        let x = arguments[0];
+        let float = |f: fn(f64) -> f64| -> Result<Number, MathError> {
+            self.context
+                .computed(f(x.to_f64()), || format!("{name}({x})"))
+        };
+        let positive = |what: &str| -> Result<(), MathError> {
+            if x.is_negative() || x.is_zero() {
+                return Err(invalid(format!(
+                    "`{name}` needs a positive {what}, got {x}"
+                )));
+            }
+            Ok(())
+        };
+
+        match name {
+            "abs" => Ok(x.abs()),
+            "floor" => Ok(x.round_dp(0, RoundingMode::Floor)),
+            "ceil" => Ok(x.round_dp(0, RoundingMode::Ceiling)),
+            "round" => {
+                let places = match arguments.get(1) {
+                    Some(places) => places
+                        .as_integer()
+                        .and_then(|places| u32::try_from(places).ok())
+                        .ok_or_else(|| {
+                            invalid(format!(
+                                "`round` needs a non-negative whole number of places, got {places}"
+                            ))
+                        })?,
+                    None => 0,
+                };
+                Ok(x.round_dp(places, self.context.rounding))
+            }
+            "sqrt" => {
+                if x.is_negative() {
+                    return Err(invalid(format!("`sqrt` of a negative number ({x})")));
+                }
+                float(f64::sqrt)
+            }
+            "cbrt" => float(f64::cbrt),
+            "exp" => float(f64::exp),
+            "ln" => {
+                positive("number")?;
+                float(f64::ln)
+            }
+            "log2" => {
+                positive("number")?;
+                float(f64::log2)
+            }
+            "log" => {
+                positive("number")?;
+                match arguments.get(1) {
+                    None => float(f64::log10),
+                    Some(base) => {
+                        let base = *base;
+                        if base.is_negative() || base.is_zero() || base.to_f64() == 1.0 {
+                            return Err(invalid(format!(
+                                "`log` needs a positive base other than 1, got {base}"
+                            )));
+                        }
+                        self.context.computed(x.to_f64().log(base.to_f64()), || {
+                            format!("log({x}, {base})")
+                        })
+                    }
+                }
+            }
+            "sin" => float(f64::sin),
+            "cos" => float(f64::cos),
+            "tan" => float(f64::tan),
+            "asin" => float(f64::asin),
+            "acos" => float(f64::acos),
+            "atan" => float(f64::atan),
+            "pow" => x.apply(BinaryOp::Power, arguments[1]),
+            "root" => self.context.root(x, arguments[1]),
+            "min" => Ok(arguments
+                .iter()
+                .copied()
+                .min_by(|a, b| a.compare(*b))
+                .expect("at least one argument")),
+            "max" => Ok(arguments
+                .iter()
+                .copied()
+                .max_by(|a, b| a.compare(*b))
+                .expect("at least one argument")),
+            _ => unreachable!("unknown functions are rejected above"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(expression: &str) -> f64 {
+        let (result, _) = evaluate(&MathContext::decimal(), expression).unwrap();
+        result.to_f64()
+    }
+
+    fn error(expression: &str) -> String {
+        evaluate(&MathContext::decimal(), expression)
+            .unwrap_err()
+            .to_string()
+    }
+
+    #[test]
+    fn follows_precedence_and_traces_every_step() {
+        let (result, steps) = evaluate(&MathContext::decimal(), "(2 + 3) / 10").unwrap();
+        assert_eq!(result.to_f64(), 0.5);
+        assert_eq!(steps.len(), 2);
+
+        assert_eq!(eval("2 + 3 * 4"), 14.0);
+        assert_eq!(eval("10 - 4 - 3"), 3.0);
+        assert_eq!(eval("2 ^ 3 ^ 2"), 512.0);
+        assert_eq!(eval("7 mod 4"), 3.0);
+    }
+
+    #[test]
+    fn keeps_decimals_exact() {
+        assert_eq!(eval("14 * 0.1 * 10"), 14.0);
+        assert_eq!(eval("0.1 + 0.2"), 0.3);
+    }
+
+    #[test]
+    fn binds_powers_tighter_than_a_leading_minus() {
+        assert_eq!(eval("-2^2"), -4.0);
+        assert_eq!(eval("2^-1"), 0.5);
+        assert_eq!(eval("2 ** 3"), 8.0);
+    }
+
+    #[test]
+    fn reads_percentages() {
+        assert_eq!(eval("10%"), 0.1);
+        assert_eq!(eval("10% of 14"), 1.4);
+    }
+
+    #[test]
+    fn reads_exponents_but_not_implicit_multiplication() {
+        assert_eq!(eval("1.5e3"), 1500.0);
+        assert_eq!(eval("2E-1"), 0.2);
+        assert!((eval("2 * e") - 2.0 * std::f64::consts::E).abs() < 1e-9);
+        assert!(error("2e").contains("unexpected `e` at position 1"));
+    }
+
+    #[test]
+    fn calls_functions() {
+        assert_eq!(eval("max(1, 3, 2)"), 3.0);
+        assert_eq!(eval("min(4, -1)"), -1.0);
+        assert_eq!(eval("sqrt(16) + abs(-2)"), 6.0);
+        assert_eq!(eval("root(27, 3)"), 3.0);
+        assert_eq!(eval("round(2.345, 2)"), 2.35);
+        assert!(error("sqrt(-1)").contains("negative"));
+        assert!(error("pow(2)").contains("takes 2 argument(s), got 1"));
+        assert!(error("foo(1)").contains("unknown function `foo`"));
+        assert!(error("tau").contains("unknown constant `tau`"));
+    }
+
+    #[test]
+    fn rejects_malformed_and_oversized_expressions() {
+        assert!(error("2 +").contains("ended unexpectedly"));
+        assert!(error("(1 + 2").contains("ended unexpectedly"));
+        assert!(error("1 + 2)").contains("unexpected `)` at position 5"));
+        assert!(error("1 $ 2").contains("unexpected `$` at position 2"));
+        assert!(error("1 / 0").contains("Division by zero"));
+        assert!(error(&"1 + ".repeat(MAX_LENGTH)).contains("is too long"));
+        assert!(
+            error(&format!("{}1{}", "(".repeat(100), ")".repeat(100)))
+                .contains("nested too deeply")
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use rig::{
+    completion::ToolDefinition,
+    tool::{Tool, ToolEmbedding},
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    InitError, MathError, definition,
+    math::{MathContext, MathOutput, Number},
+    units::{ConversionStep, ConversionTable},
+};
+
+#[derive(Deserialize, JsonSchema)]
+pub struct Args {
+    /// The amount being converted
+    value: Number,
+    /// The unit the value is in, e.g. "flurbo", "km" or "°F"
+    from: String,
+    /// The unit to convert the value to
+    to: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ConvertOutput {
+    #[serde(flatten)]
+    pub value: MathOutput,
+    pub from: String,
+    pub to: String,
+    /// how many `to` one `from` is worth, absent for conversions with an offset
+    /// (temperatures)
+    pub rate: Option<Number>,
+    pub steps: Vec<ConversionStep>,
+}
+
+pub struct Convert {
+    context: MathContext,
+    table: ConversionTable,
+}
+
+impl Convert {
+    pub fn new(context: MathContext, table: ConversionTable) -> Self {
+        Self { context, table }
+    }
+}
+
+impl Tool for Convert {
+    const NAME: &'static str = "convert";
+
+    type Error = MathError;
+    type Args = Args;
+    type Output = ConvertOutput;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        definition::<Self::Args>(
+            Self::NAME,
+            format!(
+                "Convert a value from one unit or currency to another, going through intermediate units if needed, and return the rate used. Known units: {}.",
+                self.table.units().join(", ")
+            ),
+        )
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        tracing::info!(
+            tool = "convert",
+            value = %args.value,
+            from = %args.from,
+            to = %args.to,
+            "performing operation"
+        );
+        let value = self.context.operand("value", args.value)?;
+        let (result, rate, steps) =
+            self.table
+                .convert(&self.context, value, &args.from, &args.to)?;
+
+        let resolve = |unit: &str| self.table.resolve(unit).unwrap_or(unit).to_string();
+        let output = ConvertOutput {
+            value: self.context.output(result),
+            from: resolve(&args.from),
+            to: resolve(&args.to),
+            rate,
+            steps,
+        };
+        tracing::info!(tool = "convert", result = %output.value.result, "operation result");
+        Ok(output)
+    }
+}
+
+impl ToolEmbedding for Convert {
+    type InitError = InitError;
+    type Context = MathContext;
+    type State = ConversionTable;
+
+    fn init(state: Self::State, context: Self::Context) -> Result<Self, Self::InitError> {
+        Ok(Convert::new(context, state))
+    }
+
+    fn context(&self) -> Self::Context {
+        self.context
+    }
+
+    fn embedding_docs(&self) -> Vec<String> {
+        vec![
+            "Convert a value between units or currencies (length, mass, time, temperature, money, etc), reporting the rate used.".into(),
+        ]
+    }
+}
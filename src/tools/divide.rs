@@ -3,62 +3,65 @@ use rig::{
     tool::{Tool, ToolEmbedding},
 };
 use serde::{Deserialize, Serialize};
-use serde_json::json;
 
-use super::{InitError, MathError, OperationArgs};
+use super::{
+    InitError, MathError, OperationArgs,
+    math::{BinaryOp, MathContext, MathOutput},
+    operation_definition,
+};
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct Divide {
+    context: MathContext,
+}
 
-#[derive(Deserialize, Serialize)]
-pub struct Divide;
+impl Divide {
+    pub fn new(context: MathContext) -> Self {
+        Self { context }
+    }
+}
 
 impl Tool for Divide {
     const NAME: &'static str = "divide";
 
     type Error = MathError;
     type Args = OperationArgs;
-    type Output = f64;
+    type Output = MathOutput;
 
     async fn definition(&self, _prompt: String) -> ToolDefinition {
-        serde_json::from_value(json!({
-            "name": "divide",
-            "description": "Compute the Quotient of x and y (i.e.: x / y). Useful for ratios.",
-            "parameters": {
-                "type": "object",
-                "properties": {
-                    "x": {
-                        "type": "number",
-                        "description": "The Dividend of the division. The number being divided"
-                    },
-                    "y": {
-                        "type": "number",
-                        "description": "The Divisor of the division. The number by which the dividend is being divided"
-                    }
-                }
-            }
-        }))
-        .expect("Tool Definition")
+        operation_definition(
+            Self::NAME,
+            "Compute the Quotient of x and y (i.e.: x / y), y must not be zero. Useful for ratios.",
+            "The Dividend, the number being divided",
+            "The Divisor, the number to divide by. Must not be zero",
+        )
     }
 
     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
-        println!(
-            "[divide] performing operation \"{}\" / \"{}\"",
-            args.x, args.y
+        tracing::info!(
+            tool = "divide",
+            x = %args.x,
+            y = %args.y,
+            "performing operation"
         );
-        let result = ((args.x / args.y) * 100.0).round() / 100.0;
-        println!("[divide] result: {}", result);
-        Ok(result)
+        let output = args.evaluate(&self.context, BinaryOp::Divide)?;
+        tracing::info!(tool = "divide", result = %output.result, "operation result");
+        Ok(output)
     }
 }
 
 impl ToolEmbedding for Divide {
     type InitError = InitError;
-    type Context = ();
+    type Context = MathContext;
     type State = ();
 
-    fn init(_state: Self::State, _context: Self::Context) -> Result<Self, Self::InitError> {
-        Ok(Divide)
+    fn init(_state: Self::State, context: Self::Context) -> Result<Self, Self::InitError> {
+        Ok(Divide::new(context))
     }
 
-    fn context(&self) -> Self::Context {}
+    fn context(&self) -> Self::Context {
+        self.context
+    }
 
     fn embedding_docs(&self) -> Vec<String> {
         vec!["Compute the Quotient of x and y (i.e.: x / y). Useful for ratios.".into()]
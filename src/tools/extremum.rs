@@ -0,0 +1,130 @@
+use rig::{
+    completion::ToolDefinition,
+    tool::{Tool, ToolEmbedding},
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    InitError, ListArgs, MathError, definition,
+    math::{MathContext, MathOutput},
+};
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct Min {
+    context: MathContext,
+}
+
+impl Min {
+    pub fn new(context: MathContext) -> Self {
+        Self { context }
+    }
+}
+
+impl Tool for Min {
+    const NAME: &'static str = "min";
+
+    type Error = MathError;
+    type Args = ListArgs;
+    type Output = MathOutput;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        definition::<Self::Args>(
+            Self::NAME,
+            "Find the Minimum, the smallest of a list of numbers.",
+        )
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        tracing::info!(
+            tool = "min",
+            values = ?args.values,
+            "performing operation"
+        );
+        let output = self.context.output(
+            args.operands(&self.context)?
+                .into_iter()
+                .min_by(|a, b| a.compare(*b))
+                .expect("at least one value"),
+        );
+        tracing::info!(tool = "min", result = %output.result, "operation result");
+        Ok(output)
+    }
+}
+
+impl ToolEmbedding for Min {
+    type InitError = InitError;
+    type Context = MathContext;
+    type State = ();
+
+    fn init(_state: Self::State, context: Self::Context) -> Result<Self, Self::InitError> {
+        Ok(Min::new(context))
+    }
+
+    fn context(&self) -> Self::Context {
+        self.context
+    }
+
+    fn embedding_docs(&self) -> Vec<String> {
+        vec!["Find the Minimum, the smallest of a list of numbers.".into()]
+    }
+}
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct Max {
+    context: MathContext,
+}
+
+impl Max {
+    pub fn new(context: MathContext) -> Self {
+        Self { context }
+    }
+}
+
+impl Tool for Max {
+    const NAME: &'static str = "max";
+
+    type Error = MathError;
+    type Args = ListArgs;
+    type Output = MathOutput;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        definition::<Self::Args>(
+            Self::NAME,
+            "Find the Maximum, the largest of a list of numbers.",
+        )
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        tracing::info!(
+            tool = "max",
+            values = ?args.values,
+            "performing operation"
+        );
+        let output = self.context.output(
+            args.operands(&self.context)?
+                .into_iter()
+                .max_by(|a, b| a.compare(*b))
+                .expect("at least one value"),
+        );
+        tracing::info!(tool = "max", result = %output.result, "operation result");
+        Ok(output)
+    }
+}
+
+impl ToolEmbedding for Max {
+    type InitError = InitError;
+    type Context = MathContext;
+    type State = ();
+
+    fn init(_state: Self::State, context: Self::Context) -> Result<Self, Self::InitError> {
+        Ok(Max::new(context))
+    }
+
+    fn context(&self) -> Self::Context {
+        self.context
+    }
+
+    fn embedding_docs(&self) -> Vec<String> {
+        vec!["Find the Maximum, the largest of a list of numbers.".into()]
+    }
+}
@@ -0,0 +1,366 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use rig::{completion::ToolDefinition, tool::Tool};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    MathError, definition,
+    math::{BinaryOp, MathContext, MathOutput, Number},
+};
+
+#[derive(Clone, Copy, Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// add `amount` to the balance
+    Credit,
+    /// remove `amount` from the balance
+    Debit,
+    /// remove `percent`% of the current balance
+    TransferPercentage,
+    /// the balance of `asset`, or of every asset
+    Balance,
+    /// the changes to `asset`, or to every asset
+    History,
+}
+
+/// a flat object rather than one per action, since providers don't support `oneOf`
+/// parameters. the fields each action needs are checked by `Args::required`
+#[derive(Deserialize, JsonSchema)]
+pub struct Args {
+    /// What to do with the ledger
+    action: Action,
+    /// The asset, in singular (e.g. "flurbo"). Required for credit, debit and
+    /// transfer_percentage, leaving it out of balance and history covers every asset
+    asset: Option<String>,
+    /// How much to credit or debit, must be positive
+    amount: Option<Number>,
+    /// For transfer_percentage, the percentage of the current balance to give away
+    #[schemars(range(min = 0, max = 100))]
+    percent: Option<f64>,
+    /// Where the change came from or went to, e.g. "gift from a friend"
+    note: Option<String>,
+}
+
+impl Args {
+    fn required<T: Clone>(name: &str, value: &Option<T>, action: Action) -> Result<T, MathError> {
+        value.clone().ok_or_else(|| MathError::InvalidArgument {
+            name: name.to_string(),
+            reason: format!("is required by the {action:?} action"),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    Credit,
+    Debit,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    pub asset: String,
+    pub operation: Operation,
+    pub amount: Number,
+    /// the balance of `asset` right after this entry
+    pub balance: Number,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum LedgerOutput {
+    Entry {
+        entry: LedgerEntry,
+        balance: MathOutput,
+    },
+    Balances {
+        balances: BTreeMap<String, MathOutput>,
+    },
+    History {
+        history: Vec<LedgerEntry>,
+    },
+}
+
+#[derive(Debug, Default)]
+struct LedgerState {
+    balances: BTreeMap<String, Number>,
+    entries: Vec<LedgerEntry>,
+}
+
+/// balances and history of whatever the user gains or gives away during a session
+/// (flurbos, USD, etc). clones share the same state, so the agent's tool and the
+/// `MultiTurnAgent` owning the session see the same ledger
+#[derive(Clone, Debug, Default)]
+pub struct Ledger {
+    context: MathContext,
+    state: Arc<Mutex<LedgerState>>,
+}
+
+impl Ledger {
+    pub fn new(context: MathContext) -> Self {
+        Self {
+            context,
+            state: Arc::default(),
+        }
+    }
+
+    pub fn entries(&self) -> Vec<LedgerEntry> {
+        self.state.lock().expect("ledger poisoned").entries.clone()
+    }
+
+    pub fn clear(&self) {
+        *self.state.lock().expect("ledger poisoned") = LedgerState::default();
+    }
+
+    /// replaces the ledger with previously saved entries, replaying them so the
+    /// balances are recomputed in this ledger's backend. if one of them fails, the ledger
+    /// is left as it was
+    pub fn restore(&self, entries: Vec<LedgerEntry>) -> Result<(), MathError> {
+        let previous = std::mem::take(&mut *self.state.lock().expect("ledger poisoned"));
+        let replayed = entries.into_iter().try_for_each(|entry| {
+            let amount = self.context.operand("amount", entry.amount)?;
+            self.record(&entry.asset, entry.operation, amount, entry.note)
+                .map(|_| ())
+        });
+        if replayed.is_err() {
+            *self.state.lock().expect("ledger poisoned") = previous;
+        }
+        replayed
+    }
+
+    fn record(
+        &self,
+        asset: &str,
+        operation: Operation,
+        amount: Number,
+        note: Option<String>,
+    ) -> Result<LedgerEntry, MathError> {
+        let mut state = self.state.lock().expect("ledger poisoned");
+        let current = match state.balances.get(asset) {
+            Some(balance) => *balance,
+            None => self.context.number("balance", 0.0)?,
+        };
+
+        let balance = match operation {
+            Operation::Credit => current.apply(BinaryOp::Add, amount)?,
+            Operation::Debit if amount.compare(current).is_gt() => {
+                return Err(MathError::InvalidArgument {
+                    name: "amount".to_string(),
+                    reason: format!("can't debit {amount} {asset}, the balance is only {current}"),
+                });
+            }
+            Operation::Debit => current.apply(BinaryOp::Subtract, amount)?,
+        };
+
+        let entry = LedgerEntry {
+            asset: asset.to_string(),
+            operation,
+            amount,
+            balance,
+            note,
+        };
+        state.balances.insert(asset.to_string(), balance);
+        state.entries.push(entry.clone());
+        Ok(entry)
+    }
+
+    fn balance(&self, asset: &str) -> Result<Number, MathError> {
+        let state = self.state.lock().expect("ledger poisoned");
+        match state.balances.get(asset) {
+            Some(balance) => Ok(*balance),
+            None => self.context.number("balance", 0.0),
+        }
+    }
+
+    fn amount(&self, amount: Number) -> Result<Number, MathError> {
+        let amount = self.context.operand("amount", amount)?;
+        if amount.is_negative() || amount.is_zero() {
+            return Err(MathError::InvalidArgument {
+                name: "amount".to_string(),
+                reason: format!("must be positive, got {amount}"),
+            });
+        }
+        Ok(amount)
+    }
+
+    fn entry(&self, entry: LedgerEntry) -> LedgerOutput {
+        LedgerOutput::Entry {
+            balance: self.context.output(entry.balance),
+            entry,
+        }
+    }
+}
+
+/// assets are matched case-insensitively
+fn asset_name(asset: &str) -> String {
+    asset.trim().to_lowercase()
+}
+
+impl Tool for Ledger {
+    const NAME: &'static str = "ledger";
+
+    type Error = MathError;
+    type Args = Args;
+    type Output = LedgerOutput;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        definition::<Self::Args>(
+            Self::NAME,
+            "Keep track of how much of each asset (flurbos, USD, etc) the user has during this conversation. Credit what the user receives, debit what they give away or spend, transfer a percentage of a balance, and query balances or the history of changes. Balances start at zero, so credit what the user already had first.",
+        )
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        tracing::info!(
+            tool = "ledger",
+            action = ?args.action,
+            asset = ?args.asset,
+            amount = ?args.amount,
+            percent = ?args.percent,
+            "performing operation"
+        );
+        let asset = args.asset.as_deref().map(asset_name);
+
+        let output = match args.action {
+            Action::Credit | Action::Debit => {
+                let operation = match args.action {
+                    Action::Credit => Operation::Credit,
+                    _ => Operation::Debit,
+                };
+                let asset = Args::required("asset", &asset, args.action)?;
+                let amount = self.amount(Args::required("amount", &args.amount, args.action)?)?;
+                self.entry(self.record(&asset, operation, amount, args.note)?)
+            }
+            Action::TransferPercentage => {
+                let asset = Args::required("asset", &asset, args.action)?;
+                let percent = Args::required("percent", &args.percent, args.action)?;
+                let percent = self.context.number("percent", percent)?;
+                if percent.is_negative()
+                    || percent
+                        .compare(self.context.number("percent", 100.0)?)
+                        .is_gt()
+                {
+                    return Err(MathError::InvalidArgument {
+                        name: "percent".to_string(),
+                        reason: format!("must be between 0 and 100, got {percent}"),
+                    });
+                }
+
+                let amount = self.context.percent_of(percent, self.balance(&asset)?)?;
+                let note = Some(match args.note {
+                    Some(note) => format!("{percent}% of the balance, {note}"),
+                    None => format!("{percent}% of the balance"),
+                });
+                self.entry(self.record(&asset, Operation::Debit, amount, note)?)
+            }
+            Action::Balance => {
+                let balances = match asset {
+                    Some(asset) => BTreeMap::from([(asset.clone(), self.balance(&asset)?)]),
+                    None => self.state.lock().expect("ledger poisoned").balances.clone(),
+                };
+                LedgerOutput::Balances {
+                    balances: balances
+                        .into_iter()
+                        .map(|(asset, balance)| (asset, self.context.output(balance)))
+                        .collect(),
+                }
+            }
+            Action::History => LedgerOutput::History {
+                history: self
+                    .entries()
+                    .into_iter()
+                    .filter(|entry| asset.as_ref().is_none_or(|asset| &entry.asset == asset))
+                    .collect(),
+            },
+        };
+        tracing::info!(tool = "ledger", "operation result");
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn act(ledger: &Ledger, args: serde_json::Value) -> Result<LedgerOutput, MathError> {
+        ledger.call(serde_json::from_value(args).unwrap()).await
+    }
+
+    fn balance(ledger: &Ledger, asset: &str) -> String {
+        ledger.balance(asset).unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn overdrawing_debit_is_rejected() {
+        let ledger = Ledger::new(MathContext::decimal());
+        act(
+            &ledger,
+            serde_json::json!({ "action": "credit", "asset": "Flurbo", "amount": 10 }),
+        )
+        .await
+        .unwrap();
+
+        let overdraw = act(
+            &ledger,
+            serde_json::json!({ "action": "debit", "asset": "flurbo", "amount": 10.5 }),
+        )
+        .await;
+        assert!(matches!(overdraw, Err(MathError::InvalidArgument { .. })));
+        assert_eq!(balance(&ledger, "flurbo"), "10");
+        assert_eq!(ledger.entries().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn transfer_percentage_moves_the_exact_amount() {
+        let ledger = Ledger::new(MathContext::decimal());
+        act(
+            &ledger,
+            serde_json::json!({ "action": "credit", "asset": "usd", "amount": "100.10" }),
+        )
+        .await
+        .unwrap();
+        act(
+            &ledger,
+            serde_json::json!({ "action": "transfer_percentage", "asset": "usd", "percent": 30 }),
+        )
+        .await
+        .unwrap();
+
+        let transfer = ledger.entries().pop().unwrap();
+        assert_eq!(transfer.operation, Operation::Debit);
+        assert_eq!(transfer.amount.to_string(), "30.03");
+        assert_eq!(balance(&ledger, "usd"), "70.07");
+    }
+
+    #[test]
+    fn restore_rolls_back_a_bad_snapshot() {
+        let ledger = Ledger::new(MathContext::decimal());
+        ledger
+            .record("flurbo", Operation::Credit, Number::Float(5.0), None)
+            .unwrap();
+        let before = ledger.entries();
+
+        let entry = |operation, amount| LedgerEntry {
+            asset: "gem".to_string(),
+            operation,
+            amount: Number::Float(amount),
+            balance: Number::Float(0.0),
+            note: None,
+        };
+        // the second entry takes out more than the first put in
+        let restored = ledger.restore(vec![
+            entry(Operation::Credit, 3.0),
+            entry(Operation::Debit, 4.0),
+        ]);
+
+        assert!(restored.is_err());
+        assert_eq!(ledger.entries().len(), before.len());
+        assert_eq!(balance(&ledger, "flurbo"), "5");
+        assert_eq!(balance(&ledger, "gem"), "0");
+    }
+}
@@ -3,11 +3,16 @@ use rig::{
     tool::Tool,
     vector_store::{VectorStoreError, VectorStoreIndexDyn},
 };
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
+use tracing::{Instrument, field::Empty};
 
-#[derive(Deserialize)]
+use super::{SyncFuture, definition};
+
+#[derive(Deserialize, JsonSchema)]
 pub struct Args {
+    /// The word or concept to look up, e.g. "flurbo"
     lookup: String,
 }
 
@@ -28,8 +33,17 @@ impl Lookup {
         }
     }
 
-    fn search(&self, lookup: &str) -> Result<(f64, String, Value), VectorStoreError> {
-        futures::executor::block_on(self.index.top_n(lookup, 1))?
+    /// awaited rather than blocked on, so the middleware's timeout can fire while the
+    /// query is embedded
+    async fn search(&self, lookup: &str) -> Result<(f64, String, Value), VectorStoreError> {
+        let span = tracing::info_span!("vector_search", query = lookup, n = 1, results = Empty);
+
+        let results = SyncFuture::new(self.index.top_n(lookup, 1))
+            .instrument(span.clone())
+            .await?;
+        span.record("results", results.len());
+
+        results
             .into_iter()
             .next()
             .ok_or(VectorStoreError::MissingIdError(lookup.to_string()))
@@ -44,29 +58,22 @@ impl Tool for Lookup {
     type Output = Value;
 
     async fn definition(&self, _prompt: String) -> ToolDefinition {
-        serde_json::from_value(json!({
-            "name": "lookup",
-            "description": "Looks up real and fictional concepts and returns the result, which may contain it's definition and possibly other information related to it.",
-            "parameters": {
-                "type": "object",
-                "properties": {
-                    "lookup": {
-                        "type": "string",
-                        "description": "The query to lookup"
-                    },
-                }
-            }
-        }))
-        .expect("Tool Definition")
+        definition::<Self::Args>(
+            Self::NAME,
+            "Looks up real and fictional concepts and returns the result, which may contain it's definition and possibly other information related to it.",
+        )
     }
 
     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
-        println!("[lookup] looking up \"{}\"", args.lookup);
-        let result = self.search(&args.lookup).map_err(|_| LookupError)?.2;
-        println!(
-            "[lookup] result: {:?}",
-            serde_json::to_string_pretty(&result)
-        );
+        tracing::info!(tool = "lookup", lookup = %args.lookup, "looking up");
+        let (score, id, document) = self.search(&args.lookup).await.map_err(|_| LookupError)?;
+        tracing::info!(tool = "lookup", %id, score, "lookup result");
+        // the id and score are kept so answers can cite where their information came from
+        let result = json!({
+            "id": id,
+            "score": score,
+            "document": document,
+        });
         Ok(result)
     }
 }
@@ -0,0 +1,656 @@
+use std::str::FromStr;
+
+use std::cmp::Ordering;
+
+use rust_decimal::{
+    Decimal, MathematicalOps, RoundingStrategy, prelude::FromPrimitive, prelude::ToPrimitive,
+};
+use schemars::{JsonSchema, r#gen::SchemaGenerator, schema::Schema};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error};
+
+use super::MathError;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    /// plain `f64`, fast but `0.1 * 3` is `0.30000000000000004`
+    #[default]
+    Float,
+    /// 96 bit decimal with 28 significant digits, exact for the decimals the model sends
+    Decimal,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RoundingMode {
+    /// 2.5 -> 3, -2.5 -> -3
+    #[default]
+    HalfAwayFromZero,
+    /// 2.5 -> 2, 3.5 -> 4 (banker's rounding)
+    HalfEven,
+    /// 2.7 -> 2, -2.7 -> -2
+    TowardZero,
+    /// 2.1 -> 3, -2.1 -> -3
+    AwayFromZero,
+    /// 2.7 -> 2, -2.1 -> -3
+    Floor,
+    /// 2.1 -> 3, -2.7 -> -2
+    Ceiling,
+}
+
+impl From<RoundingMode> for RoundingStrategy {
+    fn from(mode: RoundingMode) -> Self {
+        match mode {
+            RoundingMode::HalfAwayFromZero => RoundingStrategy::MidpointAwayFromZero,
+            RoundingMode::HalfEven => RoundingStrategy::MidpointNearestEven,
+            RoundingMode::TowardZero => RoundingStrategy::ToZero,
+            RoundingMode::AwayFromZero => RoundingStrategy::AwayFromZero,
+            RoundingMode::Floor => RoundingStrategy::ToNegativeInfinity,
+            RoundingMode::Ceiling => RoundingStrategy::ToPositiveInfinity,
+        }
+    }
+}
+
+/// how the math tools of an agent compute and present their results. results are
+/// always kept exact (as far as the backend allows) so they can be chained, the
+/// rounding is only applied to the `display` value meant to be shown to the user
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MathContext {
+    pub backend: Backend,
+    /// decimal places of the displayed value, `None` shows it unrounded
+    pub scale: Option<u32>,
+    pub rounding: RoundingMode,
+}
+
+impl Default for MathContext {
+    fn default() -> Self {
+        Self {
+            backend: Backend::default(),
+            scale: Some(2),
+            rounding: RoundingMode::default(),
+        }
+    }
+}
+
+impl MathContext {
+    pub fn decimal() -> Self {
+        Self {
+            backend: Backend::Decimal,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_scale(mut self, scale: Option<u32>) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_rounding(mut self, rounding: RoundingMode) -> Self {
+        self.rounding = rounding;
+        self
+    }
+
+    /// converts the argument `name` sent by the model into the backend's representation
+    pub fn number(&self, name: &str, value: f64) -> Result<Number, MathError> {
+        if !value.is_finite() {
+            return Err(MathError::InvalidArgument {
+                name: name.to_string(),
+                reason: format!("must be a finite number, got {value}"),
+            });
+        }
+
+        match self.backend {
+            Backend::Float => Ok(Number::Float(value)),
+            Backend::Decimal => {
+                to_decimal(value)
+                    .map(Number::Decimal)
+                    .ok_or_else(|| MathError::Overflow {
+                        operation: format!(
+                            "`{name}` = {value:e} (the decimal backend supports up to ±7.9e28)"
+                        ),
+                    })
+            }
+        }
+    }
+
+    /// brings an argument sent by the model into the backend. decimals read from strings
+    /// (see `Number`'s `Deserialize`) keep every digit on the decimal backend
+    pub fn operand(&self, name: &str, value: Number) -> Result<Number, MathError> {
+        match (self.backend, value) {
+            (_, Number::Float(value)) => self.number(name, value),
+            (Backend::Decimal, Number::Decimal(value)) => Ok(Number::Decimal(value)),
+            (Backend::Float, Number::Decimal(value)) => {
+                self.number(name, value.to_f64().unwrap_or(f64::NAN))
+            }
+        }
+    }
+
+    /// parses a number literal, without going through `f64` on the decimal backend
+    pub fn parse(&self, name: &str, text: &str) -> Result<Number, MathError> {
+        let value: f64 = text.parse().map_err(|_| MathError::InvalidArgument {
+            name: name.to_string(),
+            reason: format!("`{text}` is not a number"),
+        })?;
+
+        match self.backend {
+            Backend::Float => self.number(name, value),
+            Backend::Decimal => {
+                match Decimal::from_str(text).or_else(|_| Decimal::from_scientific(text)) {
+                    Ok(decimal) => Ok(Number::Decimal(decimal)),
+                    Err(_) => self.number(name, value),
+                }
+            }
+        }
+    }
+
+    /// brings the result of an `f64`-only computation (roots, logarithms, etc.) back
+    /// into the backend, `operation` describes what produced it
+    pub fn computed(
+        &self,
+        value: f64,
+        operation: impl Fn() -> String,
+    ) -> Result<Number, MathError> {
+        Number::Float(value).finite(&operation)?;
+
+        match self.backend {
+            Backend::Float => Ok(Number::Float(value)),
+            Backend::Decimal => {
+                to_decimal(value)
+                    .map(Number::Decimal)
+                    .ok_or_else(|| MathError::Overflow {
+                        operation: operation(),
+                    })
+            }
+        }
+    }
+
+    /// the `n`th root of `x`, odd roots of negative numbers are real
+    pub fn root(&self, x: Number, n: Number) -> Result<Number, MathError> {
+        if n.is_zero() {
+            return Err(MathError::InvalidArgument {
+                name: "n".to_string(),
+                reason: "can't take the 0th root".to_string(),
+            });
+        }
+        let odd = n.as_integer().is_some_and(|n| n % 2 != 0);
+        if x.is_negative() && !odd {
+            return Err(MathError::InvalidArgument {
+                name: "x".to_string(),
+                reason: format!("an even root (n = {n}) of a negative number ({x}) is not real"),
+            });
+        }
+
+        // powf would give NaN for negative bases, so the sign is put back afterwards
+        let sign = if x.is_negative() { -1.0 } else { 1.0 };
+        self.computed(sign * x.abs().to_f64().powf(1.0 / n.to_f64()), || {
+            format!("root({x}, {n})")
+        })
+    }
+
+    /// `percent`% of `x`
+    pub fn percent_of(&self, percent: Number, x: Number) -> Result<Number, MathError> {
+        percent
+            .apply(BinaryOp::Multiply, x)?
+            .apply(BinaryOp::Divide, self.hundred())
+    }
+
+    /// how much `to` grew (or shrank, if negative) relative to `from`, in percent
+    pub fn percent_change(&self, from: Number, to: Number) -> Result<Number, MathError> {
+        if from.is_zero() {
+            return Err(MathError::InvalidArgument {
+                name: "x".to_string(),
+                reason: "the change relative to zero is undefined".to_string(),
+            });
+        }
+        to.apply(BinaryOp::Subtract, from)?
+            .apply(BinaryOp::Multiply, self.hundred())?
+            .apply(BinaryOp::Divide, from.abs())
+    }
+
+    fn hundred(&self) -> Number {
+        match self.backend {
+            Backend::Float => Number::Float(100.0),
+            Backend::Decimal => Number::Decimal(Decimal::ONE_HUNDRED),
+        }
+    }
+
+    pub fn output(&self, value: Number) -> MathOutput {
+        MathOutput {
+            result: value,
+            display: self.display(value),
+        }
+    }
+
+    pub fn display(&self, value: Number) -> String {
+        let Some(scale) = self.scale else {
+            return value.to_string();
+        };
+
+        let decimal = match value {
+            Number::Decimal(value) => Some(value),
+            Number::Float(value) => Decimal::from_f64(value),
+        };
+
+        match decimal {
+            Some(decimal) => {
+                let mut rounded = decimal.round_dp_with_strategy(scale, self.rounding.into());
+                rounded.rescale(scale);
+                rounded.to_string()
+            }
+            // too big (or not finite) for a decimal, fall back to the std formatting
+            None => format!("{:.*}", scale as usize, value.to_f64()),
+        }
+    }
+}
+
+/// going through the shortest string representation keeps `0.1` as exactly 0.1
+/// instead of 0.1000000000000000055511151231257827
+fn to_decimal(value: f64) -> Option<Decimal> {
+    Decimal::from_str(&value.to_string())
+        .or_else(|_| Decimal::from_scientific(&format!("{value:e}")))
+        .ok()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Number {
+    Float(f64),
+    Decimal(Decimal),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Power,
+    Remainder,
+}
+
+impl std::fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Add => "+",
+            Self::Subtract => "-",
+            Self::Multiply => "*",
+            Self::Divide => "/",
+            Self::Power => "^",
+            Self::Remainder => "mod",
+        })
+    }
+}
+
+impl Number {
+    pub fn to_f64(self) -> f64 {
+        match self {
+            Self::Float(value) => value,
+            Self::Decimal(value) => value.to_f64().unwrap_or(f64::NAN),
+        }
+    }
+
+    pub fn is_zero(self) -> bool {
+        match self {
+            Self::Float(value) => value == 0.0,
+            Self::Decimal(value) => value.is_zero(),
+        }
+    }
+
+    pub fn is_negative(self) -> bool {
+        match self {
+            Self::Float(value) => value < 0.0,
+            Self::Decimal(value) => value.is_sign_negative() && !value.is_zero(),
+        }
+    }
+
+    /// `Some` if the number has no fractional part and fits an `i64`
+    pub fn as_integer(self) -> Option<i64> {
+        match self {
+            Self::Float(value) if value.fract() == 0.0 && value.abs() < 9.2e18 => {
+                Some(value as i64)
+            }
+            Self::Float(_) => None,
+            Self::Decimal(value) if value.fract().is_zero() => value.to_i64(),
+            Self::Decimal(_) => None,
+        }
+    }
+
+    pub fn compare(self, other: Self) -> Ordering {
+        match (self, other) {
+            (Self::Decimal(x), Self::Decimal(y)) => x.cmp(&y),
+            (x, y) => x.to_f64().total_cmp(&y.to_f64()),
+        }
+    }
+
+    pub fn neg(self) -> Self {
+        match self {
+            Self::Float(value) => Self::Float(-value),
+            Self::Decimal(value) => Self::Decimal(-value),
+        }
+    }
+
+    pub fn abs(self) -> Self {
+        match self {
+            Self::Float(value) => Self::Float(value.abs()),
+            Self::Decimal(value) => Self::Decimal(value.abs()),
+        }
+    }
+
+    /// rounds to `dp` decimal places
+    pub fn round_dp(self, dp: u32, mode: RoundingMode) -> Self {
+        match self {
+            Self::Decimal(value) => Self::Decimal(value.round_dp_with_strategy(dp, mode.into())),
+            // floats too big for a decimal have no fractional digits left to round
+            Self::Float(value) => match Decimal::from_f64(value) {
+                Some(decimal) => Self::Float(
+                    decimal
+                        .round_dp_with_strategy(dp, mode.into())
+                        .to_f64()
+                        .unwrap_or(value),
+                ),
+                None => self,
+            },
+        }
+    }
+
+    pub fn apply(self, op: BinaryOp, rhs: Self) -> Result<Self, MathError> {
+        let by_zero = match op {
+            BinaryOp::Divide | BinaryOp::Remainder => rhs.is_zero(),
+            BinaryOp::Power => self.is_zero() && rhs.is_negative(),
+            _ => false,
+        };
+        if by_zero {
+            return Err(MathError::DivisionByZero {
+                dividend: match op {
+                    BinaryOp::Power => "1".to_string(),
+                    _ => self.to_string(),
+                },
+            });
+        }
+
+        let result = match (self, rhs) {
+            (Self::Decimal(x), Self::Decimal(y)) => match op {
+                BinaryOp::Add => x.checked_add(y),
+                BinaryOp::Subtract => x.checked_sub(y),
+                BinaryOp::Multiply => x.checked_mul(y),
+                BinaryOp::Divide => x.checked_div(y),
+                BinaryOp::Remainder => x.checked_rem(y),
+                // integer powers stay exact, anything else goes through f64
+                BinaryOp::Power => match rhs.as_integer() {
+                    Some(exponent) => x.checked_powi(exponent),
+                    None => {
+                        let value = self.to_f64().powf(rhs.to_f64());
+                        if value.is_nan() {
+                            return Err(MathError::NonFinite {
+                                operation: format!("{self} {op} {rhs}"),
+                            });
+                        }
+                        to_decimal(value)
+                    }
+                },
+            }
+            .map(Self::Decimal),
+            (x, y) => {
+                let (x, y) = (x.to_f64(), y.to_f64());
+                Some(Self::Float(match op {
+                    BinaryOp::Add => x + y,
+                    BinaryOp::Subtract => x - y,
+                    BinaryOp::Multiply => x * y,
+                    BinaryOp::Divide => x / y,
+                    BinaryOp::Remainder => x % y,
+                    BinaryOp::Power => x.powf(y),
+                }))
+            }
+        };
+
+        let operation = || format!("{self} {op} {rhs}");
+        match result {
+            Some(result) => result.finite(operation),
+            None => Err(MathError::Overflow {
+                operation: operation(),
+            }),
+        }
+    }
+
+    /// makes sure a result is usable, `operation` describes what produced it
+    pub fn finite(self, operation: impl FnOnce() -> String) -> Result<Self, MathError> {
+        match self {
+            Self::Float(value) if value.is_nan() => Err(MathError::NonFinite {
+                operation: operation(),
+            }),
+            Self::Float(value) if value.is_infinite() => Err(MathError::Overflow {
+                operation: operation(),
+            }),
+            _ => Ok(self),
+        }
+    }
+}
+
+impl std::fmt::Display for Number {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            // scientific notation past the point where f64 stops being exact anyway
+            Self::Float(value) if value.abs() >= 1e16 => write!(f, "{value:e}"),
+            Self::Float(value) => write!(f, "{value}"),
+            Self::Decimal(value) => write!(f, "{}", value.normalize()),
+        }
+    }
+}
+
+/// decimals are serialized as strings with every digit, a JSON number would go through
+/// `f64` on its way to the model and back and keep only ~16 of them. floats stay plain
+/// numbers
+impl Serialize for Number {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Float(value) if value.is_finite() => serializer.serialize_f64(*value),
+            // serde_json would write `null`
+            Self::Float(value) => serializer.collect_str(value),
+            Self::Decimal(value) => serializer.collect_str(&value.normalize()),
+        }
+    }
+}
+
+/// a JSON number, read as a float, or a string holding one, read as an exact decimal
+/// when it fits one. go through `MathContext::operand` to bring it into a specific
+/// backend
+impl<'de> Deserialize<'de> for Number {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Number(f64),
+            Text(String),
+        }
+
+        match Raw::deserialize(deserializer)? {
+            Raw::Number(value) => Ok(Self::Float(value)),
+            Raw::Text(text) => {
+                let text = text.trim();
+                Decimal::from_str(text)
+                    .or_else(|_| Decimal::from_scientific(text))
+                    .map(Self::Decimal)
+                    .or_else(|_| text.parse().map(Self::Float))
+                    .map_err(|_| D::Error::custom(format!("`{text}` is not a number")))
+            }
+        }
+    }
+}
+
+/// described to the model as a plain number, numeric strings are accepted all the same
+impl JsonSchema for Number {
+    fn schema_name() -> String {
+        "Number".to_string()
+    }
+
+    fn is_referenceable() -> bool {
+        false
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        f64::json_schema(generator)
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct MathOutput {
+    /// the exact result, meant for further calculations
+    pub result: Number,
+    /// the result rounded for presentation
+    pub display: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decimal(text: &str) -> Number {
+        MathContext::decimal().parse("x", text).unwrap()
+    }
+
+    #[test]
+    fn decimal_backend_is_exact() {
+        let result = decimal("14")
+            .apply(BinaryOp::Multiply, decimal("0.1"))
+            .and_then(|x| x.apply(BinaryOp::Multiply, decimal("10")))
+            .unwrap();
+        assert_eq!(result, decimal("14"));
+        assert_eq!(result.to_string(), "14");
+
+        let sum = decimal("0.1").apply(BinaryOp::Add, decimal("0.2")).unwrap();
+        assert_eq!(sum.to_string(), "0.3");
+        // the float backend shows why it exists
+        let sum = Number::Float(0.1).apply(BinaryOp::Add, Number::Float(0.2));
+        assert_eq!(sum.unwrap().to_string(), "0.30000000000000004");
+    }
+
+    #[test]
+    fn floats_sent_by_the_model_keep_their_shortest_digits() {
+        let context = MathContext::decimal();
+        assert_eq!(context.number("x", 0.1).unwrap(), decimal("0.1"));
+        assert_eq!(
+            context.operand("x", Number::Float(2.675)).unwrap(),
+            decimal("2.675")
+        );
+    }
+
+    #[test]
+    fn division_by_zero_is_rejected() {
+        for zero in [decimal("0"), Number::Float(0.0)] {
+            let one = MathContext::default().operand("x", decimal("1")).unwrap();
+            assert!(matches!(
+                one.apply(BinaryOp::Divide, zero),
+                Err(MathError::DivisionByZero { .. })
+            ));
+            assert!(matches!(
+                decimal("1").apply(BinaryOp::Remainder, zero),
+                Err(MathError::DivisionByZero { .. })
+            ));
+            assert!(matches!(
+                zero.apply(BinaryOp::Power, decimal("-1")),
+                Err(MathError::DivisionByZero { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn overflow_is_reported() {
+        let max = Number::Decimal(Decimal::MAX);
+        assert!(matches!(
+            max.apply(BinaryOp::Multiply, decimal("2")),
+            Err(MathError::Overflow { .. })
+        ));
+        assert!(matches!(
+            Number::Float(f64::MAX).apply(BinaryOp::Multiply, Number::Float(2.0)),
+            Err(MathError::Overflow { .. })
+        ));
+        // out of the decimal range right away
+        assert!(matches!(
+            MathContext::decimal().number("x", 1e30),
+            Err(MathError::Overflow { .. })
+        ));
+    }
+
+    #[test]
+    fn nan_is_reported() {
+        assert!(matches!(
+            Number::Float(-8.0).apply(BinaryOp::Power, Number::Float(0.5)),
+            Err(MathError::NonFinite { .. })
+        ));
+        assert!(matches!(
+            decimal("-8").apply(BinaryOp::Power, decimal("0.5")),
+            Err(MathError::NonFinite { .. })
+        ));
+        assert!(matches!(
+            MathContext::decimal().number("x", f64::NAN),
+            Err(MathError::InvalidArgument { .. })
+        ));
+    }
+
+    #[test]
+    fn fractional_powers_fall_back_to_f64() {
+        let root = decimal("16")
+            .apply(BinaryOp::Power, decimal("0.5"))
+            .unwrap();
+        assert_eq!(root, decimal("4"));
+
+        let context = MathContext::decimal();
+        let cube = context.root(decimal("-27"), decimal("3")).unwrap();
+        assert_eq!(cube, decimal("-3"));
+        // integer powers never leave the decimal
+        let power = decimal("1.1").apply(BinaryOp::Power, decimal("3")).unwrap();
+        assert_eq!(power, decimal("1.331"));
+    }
+
+    #[test]
+    fn values_too_big_for_a_decimal_are_still_displayed() {
+        let context = MathContext::default();
+        let display = context.display(Number::Float(1e30));
+        assert!(display.starts_with("1000000000000000"));
+        assert!(display.ends_with(".00"));
+    }
+
+    #[test]
+    fn rounding_modes_at_a_tie() {
+        let cases = [
+            (RoundingMode::HalfAwayFromZero, "3", "-3"),
+            (RoundingMode::HalfEven, "2", "-2"),
+            (RoundingMode::TowardZero, "2", "-2"),
+            (RoundingMode::AwayFromZero, "3", "-3"),
+            (RoundingMode::Floor, "2", "-3"),
+            (RoundingMode::Ceiling, "3", "-2"),
+        ];
+        for (mode, up, down) in cases {
+            let context = MathContext::decimal()
+                .with_scale(Some(0))
+                .with_rounding(mode);
+            assert_eq!(context.display(decimal("2.5")), up, "{mode:?}");
+            assert_eq!(context.display(decimal("-2.5")), down, "{mode:?}");
+            assert_eq!(decimal("2.5").round_dp(0, mode), decimal(up), "{mode:?}");
+        }
+        // banker's rounding goes to the even neighbour either way
+        let even = MathContext::decimal().with_rounding(RoundingMode::HalfEven);
+        assert_eq!(even.display(decimal("0.125")), "0.12");
+        assert_eq!(even.display(decimal("0.135")), "0.14");
+    }
+
+    #[test]
+    fn display_uses_the_configured_places() {
+        let value = decimal("2.675");
+        assert_eq!(MathContext::decimal().display(value), "2.68");
+        assert_eq!(
+            MathContext::decimal().with_scale(Some(4)).display(value),
+            "2.6750"
+        );
+        assert_eq!(
+            MathContext::decimal().with_scale(None).display(value),
+            "2.675"
+        );
+        // the float backend rounds the shortest representation, not 2.67499999...
+        assert_eq!(MathContext::default().display(Number::Float(2.675)), "2.68");
+
+        let output = MathContext::decimal()
+            .output(decimal("1").apply(BinaryOp::Divide, decimal("3")).unwrap());
+        assert_eq!(output.display, "0.33");
+        assert!(output.result.to_string().starts_with("0.3333333333"));
+    }
+}
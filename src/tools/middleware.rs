@@ -0,0 +1,424 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use rig::{completion::ToolDefinition, tool::Tool};
+use serde_json::Value;
+
+#[derive(Debug, thiserror::Error)]
+pub enum MiddlewareError<E: std::error::Error> {
+    #[error(transparent)]
+    Tool(E),
+    #[error("Invalid arguments: {0}")]
+    Arguments(#[from] serde_json::Error),
+    #[error("`{tool}` timed out after {after:?}")]
+    Timeout { tool: String, after: Duration },
+    #[error("`{tool}` is rate limited, try again in {retry_after:?}")]
+    RateLimited { tool: String, retry_after: Duration },
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    /// including the first one
+    pub max_attempts: u32,
+    /// doubled after every failed attempt
+    pub backoff: Duration,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RateLimit {
+    pub max_calls: usize,
+    pub per: Duration,
+}
+
+/// everything the audit hooks get to see about a call
+#[derive(Clone, Debug)]
+pub struct AuditRecord {
+    pub tool: &'static str,
+    pub arguments: Value,
+    pub result: Result<Value, String>,
+    /// 0 when the tool didn't run
+    pub attempts: u32,
+    /// the result came from the session cache
+    pub cached: bool,
+    pub duration: Duration,
+}
+
+/// results of identical calls (same tool, same arguments) within a session. clones share
+/// the same entries, hand one to `MultiTurnAgent::with_tool_cache` so it is cleared with
+/// the history
+#[derive(Clone, Debug, Default)]
+pub struct ToolCache {
+    entries: Arc<Mutex<HashMap<String, Value>>>,
+}
+
+impl ToolCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().expect("tool cache poisoned").clear();
+    }
+
+    fn get(&self, key: &str) -> Option<Value> {
+        self.entries
+            .lock()
+            .expect("tool cache poisoned")
+            .get(key)
+            .cloned()
+    }
+
+    fn insert(&self, key: String, value: Value) {
+        self.entries
+            .lock()
+            .expect("tool cache poisoned")
+            .insert(key, value);
+    }
+}
+
+type AuditHook = Arc<dyn Fn(&AuditRecord) + Send + Sync>;
+
+/// wraps a tool with timeouts, retries, memoization, rate limiting and audit hooks,
+/// every layer is opt-in:
+///
+/// ```ignore
+/// .tool(
+///     Middleware::new(tools::Lookup::new(index))
+///         .with_timeout(Duration::from_secs(5))
+///         .with_retry(RetryPolicy { max_attempts: 3, backoff: Duration::from_millis(200) })
+///         .with_cache(cache.clone()),
+/// )
+/// ```
+///
+/// arguments and outputs go through `serde_json::Value` so calls can be compared and
+/// cached whatever the tool's types are
+pub struct Middleware<T: Tool> {
+    tool: T,
+    timeout: Option<Duration>,
+    retry: Option<RetryPolicy>,
+    cache: Option<ToolCache>,
+    rate_limit: Option<(RateLimit, Arc<Mutex<VecDeque<Instant>>>)>,
+    audit: Vec<AuditHook>,
+}
+
+impl<T: Tool> Middleware<T> {
+    pub fn new(tool: T) -> Self {
+        Self {
+            tool,
+            timeout: None,
+            retry: None,
+            cache: None,
+            rate_limit: None,
+            audit: Vec::new(),
+        }
+    }
+
+    /// bounds every attempt. tools blocking their thread can only be interrupted once
+    /// they yield
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// retries failed and timed out attempts, meant for tools depending on something
+    /// flaky (a network, a vector store), deterministic errors will just fail again
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// only for tools without side effects, a memoized `ledger` would stop recording
+    pub fn with_cache(mut self, cache: ToolCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// calls past the limit fail right away instead of waiting
+    pub fn with_rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        self.rate_limit = Some((rate_limit, Arc::default()));
+        self
+    }
+
+    pub fn with_audit(mut self, hook: impl Fn(&AuditRecord) + Send + Sync + 'static) -> Self {
+        self.audit.push(Arc::new(hook));
+        self
+    }
+
+    fn check_rate_limit(&self) -> Result<(), MiddlewareError<T::Error>> {
+        let Some((limit, calls)) = &self.rate_limit else {
+            return Ok(());
+        };
+        let mut calls = calls.lock().expect("rate limiter poisoned");
+        let now = Instant::now();
+        while calls
+            .front()
+            .is_some_and(|call| now.duration_since(*call) >= limit.per)
+        {
+            calls.pop_front();
+        }
+
+        if calls.len() >= limit.max_calls {
+            let oldest = calls.front().copied().unwrap_or(now);
+            return Err(MiddlewareError::RateLimited {
+                tool: T::NAME.to_string(),
+                retry_after: limit.per.saturating_sub(now.duration_since(oldest)),
+            });
+        }
+        calls.push_back(now);
+        Ok(())
+    }
+
+    async fn attempt(&self, arguments: &Value) -> Result<Value, MiddlewareError<T::Error>> {
+        let args: T::Args = serde_json::from_value(arguments.clone())?;
+        let call = self.tool.call(args);
+
+        let output =
+            match self.timeout {
+                Some(after) => tokio::time::timeout(after, call).await.map_err(|_| {
+                    MiddlewareError::Timeout {
+                        tool: T::NAME.to_string(),
+                        after,
+                    }
+                })?,
+                None => call.await,
+            }
+            .map_err(MiddlewareError::Tool)?;
+
+        Ok(serde_json::to_value(output)?)
+    }
+
+    async fn run(&self, arguments: &Value) -> (Result<Value, MiddlewareError<T::Error>>, u32) {
+        let max_attempts = self.retry.map_or(1, |retry| retry.max_attempts.max(1));
+        let mut backoff = self.retry.map(|retry| retry.backoff).unwrap_or_default();
+        let mut attempts = 0;
+
+        loop {
+            attempts += 1;
+            let result = self.attempt(arguments).await;
+            // malformed arguments won't get any better by retrying
+            let retryable = matches!(
+                result,
+                Err(MiddlewareError::Tool(_) | MiddlewareError::Timeout { .. })
+            );
+            if !retryable || attempts >= max_attempts {
+                return (result, attempts);
+            }
+
+            tracing::warn!(tool = T::NAME, attempts, "tool call failed, retrying");
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+    }
+
+    fn audit(&self, record: AuditRecord) {
+        for hook in &self.audit {
+            hook(&record);
+        }
+    }
+}
+
+impl<T: Tool> Tool for Middleware<T> {
+    const NAME: &'static str = T::NAME;
+
+    type Error = MiddlewareError<T::Error>;
+    type Args = Value;
+    type Output = Value;
+
+    async fn definition(&self, prompt: String) -> ToolDefinition {
+        self.tool.definition(prompt).await
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let started = Instant::now();
+        // `Value` objects keep their keys sorted, so identical calls give identical keys
+        let key = format!("{}:{args}", T::NAME);
+
+        if let Some(cached) = self.cache.as_ref().and_then(|cache| cache.get(&key)) {
+            tracing::info!(tool = T::NAME, "cached tool result");
+            self.audit(AuditRecord {
+                tool: T::NAME,
+                arguments: args,
+                result: Ok(cached.clone()),
+                attempts: 0,
+                cached: true,
+                duration: started.elapsed(),
+            });
+            return Ok(cached);
+        }
+
+        // rejected calls are still audited, with no attempts made
+        let (result, attempts) = match self.check_rate_limit() {
+            Ok(()) => self.run(&args).await,
+            Err(err) => (Err(err), 0),
+        };
+
+        if let (Some(cache), Ok(output)) = (&self.cache, &result) {
+            cache.insert(key, output.clone());
+        }
+        self.audit(AuditRecord {
+            tool: T::NAME,
+            arguments: args,
+            result: result
+                .as_ref()
+                .map(Value::clone)
+                .map_err(|err| err.to_string()),
+            attempts,
+            cached: false,
+            duration: started.elapsed(),
+        });
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, thiserror::Error)]
+    #[error("flaky")]
+    struct Flaky;
+
+    #[derive(Deserialize)]
+    struct Args {
+        #[serde(default)]
+        sleep_ms: u64,
+    }
+
+    /// fails its first `failures` calls, counting every call it gets
+    #[derive(Clone, Default)]
+    struct Counter {
+        calls: Arc<AtomicU32>,
+        failures: u32,
+    }
+
+    impl Tool for Counter {
+        const NAME: &'static str = "counter";
+
+        type Error = Flaky;
+        type Args = Args;
+        type Output = u32;
+
+        async fn definition(&self, _prompt: String) -> ToolDefinition {
+            ToolDefinition {
+                name: Self::NAME.to_string(),
+                description: String::new(),
+                parameters: Value::Null,
+            }
+        }
+
+        async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+            let calls = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+            tokio::time::sleep(Duration::from_millis(args.sleep_ms)).await;
+            if calls <= self.failures {
+                return Err(Flaky);
+            }
+            Ok(calls)
+        }
+    }
+
+    fn retry(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            backoff: Duration::from_millis(1),
+        }
+    }
+
+    #[tokio::test]
+    async fn slow_calls_time_out() {
+        let middleware =
+            Middleware::new(Counter::default()).with_timeout(Duration::from_millis(10));
+
+        let result = middleware
+            .call(serde_json::json!({ "sleep_ms": 1000 }))
+            .await;
+        assert!(matches!(result, Err(MiddlewareError::Timeout { .. })));
+        assert_eq!(middleware.call(serde_json::json!({})).await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn transient_errors_are_retried() {
+        let attempts = Arc::new(AtomicU32::new(0));
+        let recorded = attempts.clone();
+        let counter = Counter {
+            failures: 2,
+            ..Default::default()
+        };
+        let middleware = Middleware::new(counter.clone())
+            .with_retry(retry(3))
+            .with_audit(move |record| recorded.store(record.attempts, Ordering::SeqCst));
+
+        assert_eq!(middleware.call(serde_json::json!({})).await.unwrap(), 3);
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+        // out of attempts before the tool recovers
+        let counter = Counter {
+            failures: 5,
+            ..Default::default()
+        };
+        let middleware = Middleware::new(counter.clone()).with_retry(retry(3));
+        assert!(matches!(
+            middleware.call(serde_json::json!({})).await,
+            Err(MiddlewareError::Tool(Flaky))
+        ));
+        assert_eq!(counter.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn malformed_arguments_are_not_retried() {
+        let counter = Counter::default();
+        let middleware = Middleware::new(counter.clone()).with_retry(retry(3));
+
+        let result = middleware
+            .call(serde_json::json!({ "sleep_ms": "soon" }))
+            .await;
+        assert!(matches!(result, Err(MiddlewareError::Arguments(_))));
+        assert_eq!(counter.calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn cache_hits_skip_the_tool() {
+        let counter = Counter::default();
+        let cache = ToolCache::new();
+        let middleware = Middleware::new(counter.clone()).with_cache(cache.clone());
+
+        assert_eq!(middleware.call(serde_json::json!({})).await.unwrap(), 1);
+        assert_eq!(middleware.call(serde_json::json!({})).await.unwrap(), 1);
+        assert_eq!(counter.calls.load(Ordering::SeqCst), 1);
+
+        // different arguments, different entry
+        let other = middleware.call(serde_json::json!({ "sleep_ms": 0 })).await;
+        assert_eq!(other.unwrap(), 2);
+
+        cache.clear();
+        assert_eq!(middleware.call(serde_json::json!({})).await.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn calls_past_the_rate_limit_are_rejected() {
+        let counter = Counter::default();
+        let middleware = Middleware::new(counter.clone()).with_rate_limit(RateLimit {
+            max_calls: 2,
+            per: Duration::from_secs(60),
+        });
+
+        for _ in 0..2 {
+            middleware.call(serde_json::json!({})).await.unwrap();
+        }
+        let rejected = middleware.call(serde_json::json!({})).await;
+        match rejected {
+            Err(MiddlewareError::RateLimited { retry_after, .. }) => {
+                assert!(retry_after > Duration::from_secs(59))
+            }
+            other => panic!("expected a rate limit error, got {other:?}"),
+        }
+        assert_eq!(counter.calls.load(Ordering::SeqCst), 2);
+    }
+}
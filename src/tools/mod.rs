@@ -1,27 +1,311 @@
+use std::{
+    pin::Pin,
+    sync::Mutex,
+    task::{Context, Poll},
+};
+
+use rig::{
+    completion::ToolDefinition,
+    tool::{Tool, ToolEmbedding, ToolSet, ToolSetBuilder},
+};
+use schemars::{JsonSchema, r#gen::SchemaSettings};
 use serde::Deserialize;
+use serde_json::{Map, Value};
 
+mod abs;
 mod add;
+mod calculate;
+mod convert;
 mod divide;
+mod extremum;
+mod ledger;
 mod lookup;
+mod math;
+mod middleware;
+mod modulo;
 mod multiply;
+mod percentage;
+mod power;
+mod remember;
+mod root;
+mod round;
+mod statistics;
 mod subtract;
+mod units;
 
+pub use abs::Abs;
 pub use add::Add;
+pub use calculate::Calculate;
+pub use convert::Convert;
 pub use divide::Divide;
+pub use extremum::{Max, Min};
+pub use ledger::{Ledger, LedgerEntry};
 pub use lookup::Lookup;
+pub use math::{Backend, MathContext, RoundingMode};
+pub use middleware::{Middleware, RateLimit, RetryPolicy, ToolCache};
+pub use modulo::Modulo;
 pub use multiply::Multiply;
+pub use percentage::{PercentChange, PercentOf};
+pub use power::Power;
+pub use remember::Remember;
+pub use root::Root;
+pub use round::Round;
+pub use statistics::Statistics;
 pub use subtract::Subtract;
+pub use units::{ConversionTable, UnitDefinition};
+
+use math::{BinaryOp, MathOutput, Number};
+
+/// the names of the tools in `math_toolset`
+pub const MATH_TOOLS: &[&str] = &[
+    Add::NAME,
+    Subtract::NAME,
+    Multiply::NAME,
+    Divide::NAME,
+    Power::NAME,
+    Root::NAME,
+    Modulo::NAME,
+    PercentOf::NAME,
+    PercentChange::NAME,
+    Abs::NAME,
+    Min::NAME,
+    Max::NAME,
+    Round::NAME,
+    Statistics::NAME,
+];
+
+/// the single-operation math tools `enabled` returns `true` for, as dynamic tools. meant
+/// for `AgentBuilder::dynamic_tools` with an index of their `embedding_docs` (see
+/// `utils::VectorStore::from_tools`), so only the few relevant to a prompt are offered to
+/// the model instead of all of them on every request
+pub fn math_toolset(context: MathContext, enabled: impl Fn(&str) -> bool) -> ToolSet {
+    let builder = ToolSet::builder();
+    let builder = with_tool(builder, Add::new(context), &enabled);
+    let builder = with_tool(builder, Subtract::new(context), &enabled);
+    let builder = with_tool(builder, Multiply::new(context), &enabled);
+    let builder = with_tool(builder, Divide::new(context), &enabled);
+    let builder = with_tool(builder, Power::new(context), &enabled);
+    let builder = with_tool(builder, Root::new(context), &enabled);
+    let builder = with_tool(builder, Modulo::new(context), &enabled);
+    let builder = with_tool(builder, PercentOf::new(context), &enabled);
+    let builder = with_tool(builder, PercentChange::new(context), &enabled);
+    let builder = with_tool(builder, Abs::new(context), &enabled);
+    let builder = with_tool(builder, Min::new(context), &enabled);
+    let builder = with_tool(builder, Max::new(context), &enabled);
+    let builder = with_tool(builder, Round::new(context), &enabled);
+    let builder = with_tool(builder, Statistics::new(context), &enabled);
+    builder.build()
+}
+
+fn with_tool<T: ToolEmbedding + 'static>(
+    builder: ToolSetBuilder,
+    tool: T,
+    enabled: &impl Fn(&str) -> bool,
+) -> ToolSetBuilder {
+    if enabled(T::NAME) {
+        builder.dynamic_tool(tool)
+    } else {
+        builder
+    }
+}
+
+/// builds a tool's definition with the JSON Schema of `Args` as its parameters, so they
+/// can't drift apart. doc comments become descriptions, non-`Option` fields without a
+/// default are required
+pub fn definition<Args: JsonSchema>(name: &str, description: impl Into<String>) -> ToolDefinition {
+    let settings = SchemaSettings::draft07().with(|settings| {
+        settings.inline_subschemas = true;
+        settings.option_add_null_type = false;
+        settings.meta_schema = None;
+    });
+    let schema = settings.into_generator().into_root_schema_for::<Args>();
+    let mut parameters = serde_json::to_value(schema).expect("JSON Schema");
+    // the doc comment of `Args` itself is for whoever reads the code, the model gets
+    // `description` instead
+    if let Some(parameters) = parameters.as_object_mut() {
+        parameters.remove("description");
+    }
+    simplify(&mut parameters);
+
+    ToolDefinition {
+        name: name.to_string(),
+        description: description.into(),
+        parameters,
+    }
+}
+
+/// the definition of a tool taking `OperationArgs`, with `x` and `y` described the way the
+/// tool uses them (a dividend and a divisor, a base and an exponent, etc) instead of as
+/// plain operands
+pub fn operation_definition(
+    name: &str,
+    description: impl Into<String>,
+    x: &str,
+    y: &str,
+) -> ToolDefinition {
+    let mut definition = definition::<OperationArgs>(name, description);
+    for (operand, description) in [("x", x), ("y", y)] {
+        definition.parameters["properties"][operand]["description"] = description.into();
+    }
+    definition
+}
+
+/// providers only accept a subset of JSON Schema, so this drops the keywords they choke
+/// on (`title`, `format`) and turns documented enums (a `oneOf` of single-value enums)
+/// back into a plain `enum`, moving the docs of each value into the description
+fn simplify(schema: &mut Value) {
+    match schema {
+        Value::Object(object) => {
+            object.remove("title");
+            object.remove("format");
+            if let Some((values, notes)) = object.get("oneOf").and_then(enum_values) {
+                object.remove("oneOf");
+                object.insert("type".to_string(), "string".into());
+                object.insert("enum".to_string(), Value::Array(values));
+                if !notes.is_empty() {
+                    let description = match object.get("description").and_then(Value::as_str) {
+                        Some(description) => format!("{description} ({})", notes.join(", ")),
+                        None => notes.join(", "),
+                    };
+                    object.insert("description".to_string(), description.into());
+                }
+            }
+            object.values_mut().for_each(simplify);
+        }
+        Value::Array(values) => values.iter_mut().for_each(simplify),
+        _ => {}
+    }
+}
+
+/// the values of a `oneOf` made only of string enums, along with their descriptions
+fn enum_values(one_of: &Value) -> Option<(Vec<Value>, Vec<String>)> {
+    let mut values = Vec::new();
+    let mut notes = Vec::new();
+    for variant in one_of.as_array()? {
+        let variant: &Map<String, Value> = variant.as_object()?;
+        if variant.get("type")? != "string" {
+            return None;
+        }
+        let variant_values = variant.get("enum")?.as_array()?;
+        if let (Some(description), [value]) = (
+            variant.get("description").and_then(Value::as_str),
+            variant_values.as_slice(),
+        ) {
+            notes.push(format!("{}: {description}", value.as_str()?));
+        }
+        values.extend(variant_values.iter().cloned());
+    }
+    Some((values, notes))
+}
+
+/// `Tool::call` has to return a `Sync` future, which it isn't once it awaits one that is
+/// only `Send` (like the vector store's or the embedding model's). wrapping those in this
+/// makes it `Sync` again, the mutex is never locked since polling goes through `&mut`
+struct SyncFuture<F>(Mutex<Pin<Box<F>>>);
+
+impl<F: Future> SyncFuture<F> {
+    fn new(future: F) -> Self {
+        Self(Mutex::new(Box::pin(future)))
+    }
+}
+
+impl<F: Future> Future for SyncFuture<F> {
+    type Output = F::Output;
 
-#[derive(Deserialize)]
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.get_mut().0.get_mut() {
+            Ok(future) => future.as_mut().poll(cx),
+            Err(poisoned) => poisoned.into_inner().as_mut().poll(cx),
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
 pub struct OperationArgs {
-    x: f64,
-    y: f64,
+    /// The first operand
+    x: Number,
+    /// The second operand
+    y: Number,
+}
+
+impl OperationArgs {
+    fn operands(&self, context: &MathContext) -> Result<(Number, Number), MathError> {
+        Ok((context.operand("x", self.x)?, context.operand("y", self.y)?))
+    }
+
+    fn evaluate(&self, context: &MathContext, op: BinaryOp) -> Result<MathOutput, MathError> {
+        let (x, y) = self.operands(context)?;
+        Ok(context.output(x.apply(op, y)?))
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct UnaryArgs {
+    /// The operand
+    x: Number,
+}
+
+impl UnaryArgs {
+    fn operand(&self, context: &MathContext) -> Result<Number, MathError> {
+        context.operand("x", self.x)
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ListArgs {
+    /// The numbers to operate on, at least one
+    values: Vec<Number>,
+}
+
+impl ListArgs {
+    fn operands(&self, context: &MathContext) -> Result<Vec<Number>, MathError> {
+        if self.values.is_empty() {
+            return Err(MathError::InvalidArgument {
+                name: "values".to_string(),
+                reason: "at least one value is needed".to_string(),
+            });
+        }
+        self.values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| context.operand(&format!("values[{i}]"), *value))
+            .collect()
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
-#[error("Math error")]
-pub struct MathError;
+pub enum MathError {
+    #[error("Division by zero: {dividend} / 0 is undefined, the divisor must not be zero")]
+    DivisionByZero { dividend: String },
+    #[error("The result of {operation} is not a number (NaN), check the arguments")]
+    NonFinite { operation: String },
+    #[error("Overflow: {operation} is out of the representable range")]
+    Overflow { operation: String },
+    #[error("Invalid argument `{name}`: {reason}")]
+    InvalidArgument { name: String, reason: String },
+}
 
 #[derive(Debug, thiserror::Error)]
 #[error("Math error")]
 pub struct InitError;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn operation_definition_describes_each_operand() {
+        let divide = operation_definition("divide", "x / y", "The Dividend", "The Divisor");
+        let properties = &divide.parameters["properties"];
+
+        assert_eq!(properties["x"]["description"], "The Dividend");
+        assert_eq!(properties["y"]["description"], "The Divisor");
+        assert_eq!(divide.parameters["required"], serde_json::json!(["x", "y"]));
+        // the shared args keep their generic descriptions everywhere else
+        let plain = definition::<OperationArgs>("add", "x + y");
+        assert_eq!(
+            plain.parameters["properties"]["x"]["description"],
+            "The first operand"
+        );
+    }
+}
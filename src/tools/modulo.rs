@@ -0,0 +1,66 @@
+use rig::{
+    completion::ToolDefinition,
+    tool::{Tool, ToolEmbedding},
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    InitError, MathError, OperationArgs, definition,
+    math::{BinaryOp, MathContext, MathOutput},
+};
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct Modulo {
+    context: MathContext,
+}
+
+impl Modulo {
+    pub fn new(context: MathContext) -> Self {
+        Self { context }
+    }
+}
+
+impl Tool for Modulo {
+    const NAME: &'static str = "modulo";
+
+    type Error = MathError;
+    type Args = OperationArgs;
+    type Output = MathOutput;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        definition::<Self::Args>(
+            Self::NAME,
+            "Compute the Remainder of the division of x by y (i.e.: x mod y), y must not be zero. The result has the same sign as x. Useful for checking divisibility and wrapping values around.",
+        )
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        tracing::info!(
+            tool = "modulo",
+            x = %args.x,
+            y = %args.y,
+            "performing operation"
+        );
+        let output = args.evaluate(&self.context, BinaryOp::Remainder)?;
+        tracing::info!(tool = "modulo", result = %output.result, "operation result");
+        Ok(output)
+    }
+}
+
+impl ToolEmbedding for Modulo {
+    type InitError = InitError;
+    type Context = MathContext;
+    type State = ();
+
+    fn init(_state: Self::State, context: Self::Context) -> Result<Self, Self::InitError> {
+        Ok(Modulo::new(context))
+    }
+
+    fn context(&self) -> Self::Context {
+        self.context
+    }
+
+    fn embedding_docs(&self) -> Vec<String> {
+        vec!["Compute the Remainder of the division of x by y (i.e.: x mod y). Useful for checking divisibility and wrapping values around.".into()]
+    }
+}
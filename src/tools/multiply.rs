@@ -3,62 +3,59 @@ use rig::{
     tool::{Tool, ToolEmbedding},
 };
 use serde::{Deserialize, Serialize};
-use serde_json::json;
 
-use super::{InitError, MathError, OperationArgs};
+use super::{
+    InitError, MathError, OperationArgs, definition,
+    math::{BinaryOp, MathContext, MathOutput},
+};
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct Multiply {
+    context: MathContext,
+}
 
-#[derive(Deserialize, Serialize)]
-pub struct Multiply;
+impl Multiply {
+    pub fn new(context: MathContext) -> Self {
+        Self { context }
+    }
+}
 
 impl Tool for Multiply {
     const NAME: &'static str = "multiply";
 
     type Error = MathError;
     type Args = OperationArgs;
-    type Output = f64;
+    type Output = MathOutput;
 
     async fn definition(&self, _prompt: String) -> ToolDefinition {
-        serde_json::from_value(json!({
-            "name": "multiply",
-            "description": "Compute the product of x and y (i.e.: x * y)",
-            "parameters": {
-                "type": "object",
-                "properties": {
-                    "x": {
-                        "type": "number",
-                        "description": "The first factor in the product"
-                    },
-                    "y": {
-                        "type": "number",
-                        "description": "The second factor in the product"
-                    }
-                }
-            }
-        }))
-        .expect("Tool Definition")
+        definition::<Self::Args>(Self::NAME, "Compute the product of x and y (i.e.: x * y)")
     }
 
     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
-        println!(
-            "[multiply] performing operation \"{}\" * \"{}\"",
-            args.x, args.y
+        tracing::info!(
+            tool = "multiply",
+            x = %args.x,
+            y = %args.y,
+            "performing operation"
         );
-        let result = ((args.x * args.y) * 100.0).round() / 100.0;
-        println!("[multiply] result: {}", result);
-        Ok(result)
+        let output = args.evaluate(&self.context, BinaryOp::Multiply)?;
+        tracing::info!(tool = "multiply", result = %output.result, "operation result");
+        Ok(output)
     }
 }
 
 impl ToolEmbedding for Multiply {
     type InitError = InitError;
-    type Context = ();
+    type Context = MathContext;
     type State = ();
 
-    fn init(_state: Self::State, _context: Self::Context) -> Result<Self, Self::InitError> {
-        Ok(Multiply)
+    fn init(_state: Self::State, context: Self::Context) -> Result<Self, Self::InitError> {
+        Ok(Multiply::new(context))
     }
 
-    fn context(&self) -> Self::Context {}
+    fn context(&self) -> Self::Context {
+        self.context
+    }
 
     fn embedding_docs(&self) -> Vec<String> {
         vec!["Compute the product of x and y (i.e.: x * y)".into()]
@@ -0,0 +1,129 @@
+use rig::{
+    completion::ToolDefinition,
+    tool::{Tool, ToolEmbedding},
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    InitError, MathError, OperationArgs,
+    math::{MathContext, MathOutput},
+    operation_definition,
+};
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct PercentOf {
+    context: MathContext,
+}
+
+impl PercentOf {
+    pub fn new(context: MathContext) -> Self {
+        Self { context }
+    }
+}
+
+impl Tool for PercentOf {
+    const NAME: &'static str = "percent_of";
+
+    type Error = MathError;
+    type Args = OperationArgs;
+    type Output = MathOutput;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        operation_definition(
+            Self::NAME,
+            "Compute x percent of y (i.e.: x% of y, x * y / 100). Useful for discounts, taxes, tips and shares of a total.",
+            "The Percentage to take, e.g. 15 for 15% (not 0.15)",
+            "The Whole the percentage is taken of",
+        )
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        tracing::info!(
+            tool = "percent_of",
+            x = %args.x,
+            y = %args.y,
+            "performing operation"
+        );
+        let (x, y) = args.operands(&self.context)?;
+        let output = self.context.output(self.context.percent_of(x, y)?);
+        tracing::info!(tool = "percent_of", result = %output.result, "operation result");
+        Ok(output)
+    }
+}
+
+impl ToolEmbedding for PercentOf {
+    type InitError = InitError;
+    type Context = MathContext;
+    type State = ();
+
+    fn init(_state: Self::State, context: Self::Context) -> Result<Self, Self::InitError> {
+        Ok(PercentOf::new(context))
+    }
+
+    fn context(&self) -> Self::Context {
+        self.context
+    }
+
+    fn embedding_docs(&self) -> Vec<String> {
+        vec!["Compute x percent of y (i.e.: x% of y, x * y / 100). Useful for discounts, taxes, tips and shares of a total.".into()]
+    }
+}
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct PercentChange {
+    context: MathContext,
+}
+
+impl PercentChange {
+    pub fn new(context: MathContext) -> Self {
+        Self { context }
+    }
+}
+
+impl Tool for PercentChange {
+    const NAME: &'static str = "percent_change";
+
+    type Error = MathError;
+    type Args = OperationArgs;
+    type Output = MathOutput;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        operation_definition(
+            Self::NAME,
+            "Compute the Percentage Change going from x to y (i.e.: (y - x) / |x| * 100). x must not be zero. Positive for an increase, negative for a decrease.",
+            "The Original value the change is measured from. Must not be zero",
+            "The New value",
+        )
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        tracing::info!(
+            tool = "percent_change",
+            x = %args.x,
+            y = %args.y,
+            "performing operation"
+        );
+        let (x, y) = args.operands(&self.context)?;
+        let output = self.context.output(self.context.percent_change(x, y)?);
+        tracing::info!(tool = "percent_change", result = %output.result, "operation result");
+        Ok(output)
+    }
+}
+
+impl ToolEmbedding for PercentChange {
+    type InitError = InitError;
+    type Context = MathContext;
+    type State = ();
+
+    fn init(_state: Self::State, context: Self::Context) -> Result<Self, Self::InitError> {
+        Ok(PercentChange::new(context))
+    }
+
+    fn context(&self) -> Self::Context {
+        self.context
+    }
+
+    fn embedding_docs(&self) -> Vec<String> {
+        vec!["Compute the Percentage Change going from x to y (i.e.: (y - x) / |x| * 100). Positive for an increase, negative for a decrease.".into()]
+    }
+}
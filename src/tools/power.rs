@@ -0,0 +1,69 @@
+use rig::{
+    completion::ToolDefinition,
+    tool::{Tool, ToolEmbedding},
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    InitError, MathError, OperationArgs,
+    math::{BinaryOp, MathContext, MathOutput},
+    operation_definition,
+};
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct Power {
+    context: MathContext,
+}
+
+impl Power {
+    pub fn new(context: MathContext) -> Self {
+        Self { context }
+    }
+}
+
+impl Tool for Power {
+    const NAME: &'static str = "power";
+
+    type Error = MathError;
+    type Args = OperationArgs;
+    type Output = MathOutput;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        operation_definition(
+            Self::NAME,
+            "Raise x to the power of y (i.e.: x ^ y). Useful for squares, cubes and compound growth.",
+            "The Base, the number being raised",
+            "The Exponent, the power the base is raised to. May be negative or fractional, 0.5 is a square root",
+        )
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        tracing::info!(
+            tool = "power",
+            x = %args.x,
+            y = %args.y,
+            "performing operation"
+        );
+        let output = args.evaluate(&self.context, BinaryOp::Power)?;
+        tracing::info!(tool = "power", result = %output.result, "operation result");
+        Ok(output)
+    }
+}
+
+impl ToolEmbedding for Power {
+    type InitError = InitError;
+    type Context = MathContext;
+    type State = ();
+
+    fn init(_state: Self::State, context: Self::Context) -> Result<Self, Self::InitError> {
+        Ok(Power::new(context))
+    }
+
+    fn context(&self) -> Self::Context {
+        self.context
+    }
+
+    fn embedding_docs(&self) -> Vec<String> {
+        vec!["Raise x to the power of y (i.e.: x ^ y). Useful for squares, cubes and compound growth.".into()]
+    }
+}
@@ -0,0 +1,89 @@
+use rig::{completion::ToolDefinition, embeddings::EmbeddingModel, tool::Tool};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::{SyncFuture, definition};
+use crate::utils::{KnowledgeBase, WordDefinition};
+
+#[derive(Deserialize, JsonSchema)]
+pub struct Args {
+    /// The word or concept being defined, e.g. "flurbo"
+    word: String,
+    /// What the word means, as a full sentence
+    definition: String,
+    /// Replace the definitions already known for the word instead of adding to them.
+    /// Defaults to false
+    #[serde(default)]
+    replace: bool,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RememberError {
+    #[error("Invalid argument `{name}`: {reason}")]
+    InvalidArgument { name: String, reason: String },
+    #[error("Could not remember `{word}`: {reason}")]
+    Store { word: String, reason: String },
+}
+
+#[derive(Serialize)]
+pub struct RememberOutput {
+    #[serde(flatten)]
+    entry: WordDefinition,
+    /// `false` while the entry waits for review
+    searchable: bool,
+}
+
+pub struct Remember<M: EmbeddingModel> {
+    knowledge: KnowledgeBase<M>,
+}
+
+impl<M: EmbeddingModel> Remember<M> {
+    pub fn new(knowledge: KnowledgeBase<M>) -> Self {
+        Self { knowledge }
+    }
+}
+
+impl<M: EmbeddingModel + 'static> Tool for Remember<M> {
+    const NAME: &'static str = "remember";
+
+    type Error = RememberError;
+    type Args = Args;
+    type Output = RememberOutput;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        definition::<Self::Args>(
+            Self::NAME,
+            "Saves the definition of a word or concept to the knowledge base, so it can be looked up later. Use it when the user teaches you something new or corrects a definition.",
+        )
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        tracing::info!(
+            tool = "remember",
+            word = %args.word,
+            replace = args.replace,
+            "remembering"
+        );
+        for (name, value) in [("word", &args.word), ("definition", &args.definition)] {
+            if value.trim().is_empty() {
+                return Err(RememberError::InvalidArgument {
+                    name: name.to_string(),
+                    reason: "must not be empty".to_string(),
+                });
+            }
+        }
+
+        let (entry, searchable) = SyncFuture::new(self.knowledge.remember(
+            args.word.trim(),
+            args.definition.trim(),
+            args.replace,
+        ))
+        .await
+        .map_err(|err| RememberError::Store {
+            word: args.word.clone(),
+            reason: err.to_string(),
+        })?;
+        tracing::info!(tool = "remember", id = %entry.id, searchable, "remembered");
+        Ok(RememberOutput { entry, searchable })
+    }
+}
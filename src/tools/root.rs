@@ -0,0 +1,78 @@
+use rig::{
+    completion::ToolDefinition,
+    tool::{Tool, ToolEmbedding},
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    InitError, MathError, definition,
+    math::{MathContext, MathOutput, Number},
+};
+
+#[derive(Deserialize, JsonSchema)]
+pub struct RootArgs {
+    /// The Radicand, the number whose root is taken. May only be negative for odd roots
+    x: Number,
+    /// The Degree of the root, 2 for a square root, 3 for a cube root and so on. Must not
+    /// be zero
+    n: Number,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct Root {
+    context: MathContext,
+}
+
+impl Root {
+    pub fn new(context: MathContext) -> Self {
+        Self { context }
+    }
+}
+
+impl Tool for Root {
+    const NAME: &'static str = "root";
+
+    type Error = MathError;
+    type Args = RootArgs;
+    type Output = MathOutput;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        definition::<Self::Args>(
+            Self::NAME,
+            "Compute the n-th Root of x (i.e.: the number that raised to n gives x). Useful for square roots, cube roots and average growth rates.",
+        )
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        tracing::info!(
+            tool = "root",
+            x = %args.x,
+            n = %args.n,
+            "performing operation"
+        );
+        let x = self.context.operand("x", args.x)?;
+        let n = self.context.operand("n", args.n)?;
+        let output = self.context.output(self.context.root(x, n)?);
+        tracing::info!(tool = "root", result = %output.result, "operation result");
+        Ok(output)
+    }
+}
+
+impl ToolEmbedding for Root {
+    type InitError = InitError;
+    type Context = MathContext;
+    type State = ();
+
+    fn init(_state: Self::State, context: Self::Context) -> Result<Self, Self::InitError> {
+        Ok(Root::new(context))
+    }
+
+    fn context(&self) -> Self::Context {
+        self.context
+    }
+
+    fn embedding_docs(&self) -> Vec<String> {
+        vec!["Compute the n-th Root of x (i.e.: the number that raised to n gives x). Useful for square roots, cube roots and average growth rates.".into()]
+    }
+}
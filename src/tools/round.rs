@@ -0,0 +1,108 @@
+use rig::{
+    completion::ToolDefinition,
+    tool::{Tool, ToolEmbedding},
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    InitError, MathError, definition,
+    math::{MathContext, MathOutput, Number, RoundingMode},
+};
+
+#[derive(Deserialize, JsonSchema)]
+pub struct RoundArgs {
+    /// The number being rounded
+    x: Number,
+    /// How many decimal places to keep. Defaults to 0 (a whole number)
+    // advertised as an integer, but read as a float since providers may send `2.0`
+    #[schemars(with = "Option<u32>", range(max = 28))]
+    places: Option<f64>,
+    /// How ties and the discarded digits are handled. Defaults to the configured mode
+    mode: Option<RoundingMode>,
+}
+
+impl RoundArgs {
+    /// more places than a decimal can hold would leave the number untouched anyway
+    const MAX_PLACES: u32 = 28;
+
+    fn places(&self) -> Result<u32, MathError> {
+        let Some(places) = self.places else {
+            return Ok(0);
+        };
+        if places.fract() != 0.0 || !(0.0..=Self::MAX_PLACES as f64).contains(&places) {
+            return Err(MathError::InvalidArgument {
+                name: "places".to_string(),
+                reason: format!(
+                    "must be a whole number between 0 and {}, got {places}",
+                    Self::MAX_PLACES
+                ),
+            });
+        }
+        Ok(places as u32)
+    }
+}
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct Round {
+    context: MathContext,
+}
+
+impl Round {
+    pub fn new(context: MathContext) -> Self {
+        Self { context }
+    }
+}
+
+impl Tool for Round {
+    const NAME: &'static str = "round";
+
+    type Error = MathError;
+    type Args = RoundArgs;
+    type Output = MathOutput;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        definition::<Self::Args>(
+            Self::NAME,
+            "Round x to a number of decimal places. Only use it when the user explicitly asks for a rounded value, the other tools already return a rounded \"display\" value.",
+        )
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        tracing::info!(
+            tool = "round",
+            x = %args.x,
+            places = ?args.places,
+            mode = ?args.mode,
+            "performing operation"
+        );
+        let x = self.context.operand("x", args.x)?;
+        let places = args.places()?;
+        let mode = args.mode.unwrap_or(self.context.rounding);
+        // the display keeps exactly the requested places instead of the context's scale
+        let output = self
+            .context
+            .with_scale(Some(places))
+            .output(x.round_dp(places, mode));
+        tracing::info!(tool = "round", result = %output.result, "operation result");
+        Ok(output)
+    }
+}
+
+impl ToolEmbedding for Round {
+    type InitError = InitError;
+    type Context = MathContext;
+    type State = ();
+
+    fn init(_state: Self::State, context: Self::Context) -> Result<Self, Self::InitError> {
+        Ok(Round::new(context))
+    }
+
+    fn context(&self) -> Self::Context {
+        self.context
+    }
+
+    fn embedding_docs(&self) -> Vec<String> {
+        vec!["Round x to a number of decimal places. Only use it when the user explicitly asks for a rounded value, the other tools already return a rounded \"display\" value.".into()]
+    }
+}
@@ -0,0 +1,290 @@
+use std::cmp::Ordering;
+
+use rig::{
+    completion::ToolDefinition,
+    tool::{Tool, ToolEmbedding},
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    InitError, ListArgs, MathError, definition,
+    math::{BinaryOp, MathContext, MathOutput, Number},
+};
+
+/// quartiles, reported when the model doesn't ask for specific percentiles
+const DEFAULT_PERCENTILES: [f64; 3] = [25.0, 50.0, 75.0];
+
+#[derive(Deserialize, JsonSchema)]
+pub struct StatisticsArgs {
+    #[serde(flatten)]
+    list: ListArgs,
+    /// The percentiles to compute, each between 0 and 100. Defaults to the quartiles
+    /// (25, 50 and 75)
+    percentiles: Option<Vec<f64>>,
+    /// Whether the values are a sample of a bigger population, dividing the variance by
+    /// n - 1 instead of n. Defaults to false
+    #[serde(default)]
+    sample: bool,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Percentile {
+    pub percentile: f64,
+    #[serde(flatten)]
+    pub value: MathOutput,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct StatisticsOutput {
+    pub count: usize,
+    pub sum: MathOutput,
+    pub mean: MathOutput,
+    pub median: MathOutput,
+    /// every value sharing the highest frequency, empty if no value repeats
+    pub mode: Vec<MathOutput>,
+    pub variance: MathOutput,
+    pub standard_deviation: MathOutput,
+    pub min: MathOutput,
+    pub max: MathOutput,
+    pub percentiles: Vec<Percentile>,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct Statistics {
+    context: MathContext,
+}
+
+impl Statistics {
+    pub fn new(context: MathContext) -> Self {
+        Self { context }
+    }
+
+    fn summarize(&self, args: &StatisticsArgs) -> Result<StatisticsOutput, MathError> {
+        let context = &self.context;
+        let mut values = args.list.operands(context)?;
+        values.sort_by(|a, b| a.compare(*b));
+
+        let count = values.len();
+        if args.sample && count < 2 {
+            return Err(MathError::InvalidArgument {
+                name: "values".to_string(),
+                reason: "the sample variance needs at least two values".to_string(),
+            });
+        }
+        let percentiles = args
+            .percentiles
+            .clone()
+            .unwrap_or_else(|| DEFAULT_PERCENTILES.to_vec());
+        for (i, percentile) in percentiles.iter().enumerate() {
+            if !(0.0..=100.0).contains(percentile) {
+                return Err(MathError::InvalidArgument {
+                    name: format!("percentiles[{i}]"),
+                    reason: format!("must be between 0 and 100, got {percentile}"),
+                });
+            }
+        }
+
+        let sum = values
+            .iter()
+            .skip(1)
+            .try_fold(values[0], |sum, value| sum.apply(BinaryOp::Add, *value))?;
+        let mean = sum.apply(BinaryOp::Divide, context.number("count", count as f64)?)?;
+
+        let squared_deviations = values.iter().try_fold(
+            context.number("sum", 0.0)?,
+            |total, value| -> Result<Number, MathError> {
+                let deviation = value.apply(BinaryOp::Subtract, mean)?;
+                total.apply(
+                    BinaryOp::Add,
+                    deviation.apply(BinaryOp::Multiply, deviation)?,
+                )
+            },
+        )?;
+        let degrees_of_freedom = if args.sample { count - 1 } else { count };
+        let variance = squared_deviations.apply(
+            BinaryOp::Divide,
+            context.number("count", degrees_of_freedom as f64)?,
+        )?;
+        let standard_deviation = context.root(variance, context.number("n", 2.0)?)?;
+
+        Ok(StatisticsOutput {
+            count,
+            sum: context.output(sum),
+            mean: context.output(mean),
+            median: context.output(percentile(context, &values, 50.0)?),
+            mode: mode(&values)
+                .into_iter()
+                .map(|value| context.output(value))
+                .collect(),
+            variance: context.output(variance),
+            standard_deviation: context.output(standard_deviation),
+            min: context.output(values[0]),
+            max: context.output(values[count - 1]),
+            percentiles: percentiles
+                .into_iter()
+                .map(|p| {
+                    Ok(Percentile {
+                        percentile: p,
+                        value: context.output(percentile(context, &values, p)?),
+                    })
+                })
+                .collect::<Result<_, MathError>>()?,
+        })
+    }
+}
+
+/// linear interpolation between the closest ranks, `sorted` must not be empty
+fn percentile(context: &MathContext, sorted: &[Number], p: f64) -> Result<Number, MathError> {
+    let rank = p / 100.0 * (sorted.len() - 1) as f64;
+    let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+    if lower == upper {
+        return Ok(sorted[lower]);
+    }
+
+    let fraction = context.number("fraction", rank - lower as f64)?;
+    let gap = sorted[upper].apply(BinaryOp::Subtract, sorted[lower])?;
+    sorted[lower].apply(BinaryOp::Add, gap.apply(BinaryOp::Multiply, fraction)?)
+}
+
+/// `sorted` must be sorted, so equal values are next to each other
+fn mode(sorted: &[Number]) -> Vec<Number> {
+    let mut runs: Vec<(Number, usize)> = Vec::new();
+    for value in sorted {
+        match runs.last_mut() {
+            Some((last, count)) if last.compare(*value) == Ordering::Equal => *count += 1,
+            _ => runs.push((*value, 1)),
+        }
+    }
+
+    let highest = runs.iter().map(|(_, count)| *count).max().unwrap_or(0);
+    if highest < 2 {
+        return Vec::new();
+    }
+    runs.into_iter()
+        .filter(|(_, count)| *count == highest)
+        .map(|(value, _)| value)
+        .collect()
+}
+
+impl Tool for Statistics {
+    const NAME: &'static str = "statistics";
+
+    type Error = MathError;
+    type Args = StatisticsArgs;
+    type Output = StatisticsOutput;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        definition::<Self::Args>(
+            Self::NAME,
+            "Describe a list of numbers at once: count, sum, mean (average), median, mode, variance, standard deviation, min, max and percentiles. Useful for averages and spreads of balances, prices, scores, etc.",
+        )
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        tracing::info!(
+            tool = "statistics",
+            values = ?args.list.values,
+            percentiles = ?args.percentiles,
+            sample = args.sample,
+            "performing operation"
+        );
+        let output = self.summarize(&args)?;
+        tracing::info!(
+            tool = "statistics",
+            count = output.count,
+            mean = %output.mean.result,
+            "operation result"
+        );
+        Ok(output)
+    }
+}
+
+impl ToolEmbedding for Statistics {
+    type InitError = InitError;
+    type Context = MathContext;
+    type State = ();
+
+    fn init(_state: Self::State, context: Self::Context) -> Result<Self, Self::InitError> {
+        Ok(Statistics::new(context))
+    }
+
+    fn context(&self) -> Self::Context {
+        self.context
+    }
+
+    fn embedding_docs(&self) -> Vec<String> {
+        vec![
+            "Describe a list of numbers: sum, mean (average), median, mode, variance, standard deviation, min, max and percentiles.".into(),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summarize(args: serde_json::Value) -> StatisticsOutput {
+        Statistics::new(MathContext::decimal())
+            .summarize(&serde_json::from_value(args).unwrap())
+            .unwrap()
+    }
+
+    fn results(outputs: &[MathOutput]) -> Vec<String> {
+        outputs
+            .iter()
+            .map(|output| output.result.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn percentiles_interpolate_between_ranks() {
+        let output = summarize(serde_json::json!({
+            "values": [4, 1, 3, 2],
+            "percentiles": [0, 50, 100, 25],
+        }));
+
+        assert_eq!(output.median.result.to_string(), "2.5");
+        let percentiles: Vec<_> = output
+            .percentiles
+            .iter()
+            .map(|p| (p.percentile, p.value.result.to_string()))
+            .collect();
+        assert_eq!(
+            percentiles,
+            [
+                (0.0, "1".to_string()),
+                (50.0, "2.5".to_string()),
+                (100.0, "4".to_string()),
+                (25.0, "1.75".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn every_most_frequent_value_is_a_mode() {
+        let output = summarize(serde_json::json!({ "values": [3, 1, 3, 2, 1, 5] }));
+        assert_eq!(results(&output.mode), ["1", "3"]);
+
+        let output = summarize(serde_json::json!({ "values": [3, 1, 2] }));
+        assert!(output.mode.is_empty());
+    }
+
+    #[test]
+    fn sample_variance_divides_by_n_minus_one() {
+        let values = serde_json::json!([2, 4, 4, 4, 5, 5, 7, 9]);
+        let population = summarize(serde_json::json!({ "values": values }));
+        let sample = summarize(serde_json::json!({ "values": values, "sample": true }));
+
+        assert_eq!(population.variance.result.to_string(), "4");
+        assert_eq!(population.standard_deviation.result.to_string(), "2");
+        // 32 / 7
+        assert_eq!(sample.variance.display, "4.57");
+        assert_eq!(sample.standard_deviation.display, "2.14");
+
+        let single = Statistics::default().summarize(
+            &serde_json::from_value(serde_json::json!({ "values": [1], "sample": true })).unwrap(),
+        );
+        assert!(matches!(single, Err(MathError::InvalidArgument { .. })));
+    }
+}
@@ -3,62 +3,59 @@ use rig::{
     tool::{Tool, ToolEmbedding},
 };
 use serde::{Deserialize, Serialize};
-use serde_json::json;
 
-use super::{InitError, MathError, OperationArgs};
+use super::{
+    InitError, MathError, OperationArgs, definition,
+    math::{BinaryOp, MathContext, MathOutput},
+};
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct Subtract {
+    context: MathContext,
+}
 
-#[derive(Deserialize, Serialize)]
-pub struct Subtract;
+impl Subtract {
+    pub fn new(context: MathContext) -> Self {
+        Self { context }
+    }
+}
 
 impl Tool for Subtract {
     const NAME: &'static str = "subtract";
 
     type Error = MathError;
     type Args = OperationArgs;
-    type Output = f64;
+    type Output = MathOutput;
 
     async fn definition(&self, _prompt: String) -> ToolDefinition {
-        serde_json::from_value(json!({
-            "name": "subtract",
-            "description": "Subtract y from x (i.e.: x - y)",
-            "parameters": {
-                "type": "object",
-                "properties": {
-                    "x": {
-                        "type": "number",
-                        "description": "The number to subtract from"
-                    },
-                    "y": {
-                        "type": "number",
-                        "description": "The number to subtract"
-                    }
-                }
-            }
-        }))
-        .expect("Tool Definition")
+        definition::<Self::Args>(Self::NAME, "Subtract y from x (i.e.: x - y)")
     }
 
     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
-        println!(
-            "[subtract] performing operation \"{}\" - \"{}\"",
-            args.x, args.y
+        tracing::info!(
+            tool = "subtract",
+            x = %args.x,
+            y = %args.y,
+            "performing operation"
         );
-        let result = ((args.x - args.y) * 100.0).round() / 100.0;
-        println!("[subtract] result: {}", result);
-        Ok(result)
+        let output = args.evaluate(&self.context, BinaryOp::Subtract)?;
+        tracing::info!(tool = "subtract", result = %output.result, "operation result");
+        Ok(output)
     }
 }
 
 impl ToolEmbedding for Subtract {
     type InitError = InitError;
-    type Context = ();
+    type Context = MathContext;
     type State = ();
 
-    fn init(_state: Self::State, _context: Self::Context) -> Result<Self, Self::InitError> {
-        Ok(Subtract)
+    fn init(_state: Self::State, context: Self::Context) -> Result<Self, Self::InitError> {
+        Ok(Subtract::new(context))
     }
 
-    fn context(&self) -> Self::Context {}
+    fn context(&self) -> Self::Context {
+        self.context
+    }
 
     fn embedding_docs(&self) -> Vec<String> {
         vec!["Subtract y from x (i.e.: x - y)".into()]
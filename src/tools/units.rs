@@ -0,0 +1,473 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    MathError,
+    math::{BinaryOp, MathContext, Number},
+};
+
+/// `1 name = factor unit + offset`, the offset is only ever non-zero for scales that
+/// don't start at zero, like temperatures
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct UnitDefinition {
+    pub name: String,
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    pub factor: f64,
+    pub unit: String,
+    #[serde(default)]
+    pub offset: f64,
+}
+
+impl UnitDefinition {
+    pub fn new(name: impl Into<String>, factor: f64, unit: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            aliases: Vec::new(),
+            factor,
+            unit: unit.into(),
+            offset: 0.0,
+        }
+    }
+
+    pub fn with_aliases(mut self, aliases: &[&str]) -> Self {
+        self.aliases
+            .extend(aliases.iter().map(|alias| alias.to_string()));
+        self
+    }
+
+    pub fn with_offset(mut self, offset: f64) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// parses `<amount> <name> = <amount> <unit>`, e.g. `1 flurbo = 10 USD`
+    pub fn parse(text: &str) -> Option<Self> {
+        let (lhs, rhs) = text.split_once('=')?;
+        let amount = |side: &str| -> Option<(f64, String)> {
+            let (amount, unit) = side.trim().split_once(char::is_whitespace)?;
+            let amount: f64 = amount.replace(',', "").parse().ok()?;
+            let unit = unit.trim();
+            (amount.is_finite() && amount != 0.0 && !unit.is_empty())
+                .then(|| (amount, unit.to_string()))
+        };
+
+        let (lhs_amount, name) = amount(lhs)?;
+        let (rhs_amount, unit) = amount(rhs)?;
+        Some(Self::new(name, rhs_amount / lhs_amount, unit))
+    }
+
+    /// every `[convert: 1 flurbo = 10 USD]` tag found in `text`
+    pub fn extract_tags(text: &str) -> Vec<Self> {
+        let mut definitions = Vec::new();
+        let mut rest = text;
+
+        while let Some(start) = rest.find("[convert:") {
+            rest = &rest[start + "[convert:".len()..];
+            let Some(end) = rest.find(']') else {
+                break;
+            };
+            if let Some(definition) = Self::parse(&rest[..end]) {
+                definitions.push(definition);
+            }
+            rest = &rest[end + 1..];
+        }
+
+        definitions
+    }
+}
+
+/// the definition used to go from one unit to the next, possibly backwards
+#[derive(Clone, Debug)]
+struct Edge {
+    to: String,
+    definition: usize,
+    inverse: bool,
+}
+
+/// every unit the `convert` tool knows about, as a graph of definitions so a conversion
+/// can go through as many intermediate units as needed (flurbo -> USD -> EUR)
+#[derive(Clone, Debug, Default)]
+pub struct ConversionTable {
+    definitions: Vec<UnitDefinition>,
+    /// lowercase name or alias -> unit name
+    names: HashMap<String, String>,
+    edges: HashMap<String, Vec<Edge>>,
+}
+
+/// a single hop of a conversion
+#[derive(Clone, Debug, Serialize)]
+pub struct ConversionStep {
+    pub from: String,
+    pub to: String,
+    /// the definition this hop came from, as written
+    pub definition: String,
+}
+
+impl ConversionTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// common length, mass, time, volume, speed, data and temperature units, no
+    /// currencies since their rates change all the time
+    pub fn builtin() -> Self {
+        Self::new().with_definitions([
+            UnitDefinition::new("meter", 1.0, "meter").with_aliases(&["m", "metre"]),
+            UnitDefinition::new("kilometer", 1000.0, "meter").with_aliases(&["km", "kilometre"]),
+            UnitDefinition::new("centimeter", 0.01, "meter").with_aliases(&["cm", "centimetre"]),
+            UnitDefinition::new("millimeter", 0.001, "meter").with_aliases(&["mm", "millimetre"]),
+            UnitDefinition::new("mile", 1609.344, "meter").with_aliases(&["mi"]),
+            UnitDefinition::new("yard", 0.9144, "meter").with_aliases(&["yd"]),
+            UnitDefinition::new("foot", 0.3048, "meter").with_aliases(&["ft", "feet"]),
+            UnitDefinition::new("inch", 0.0254, "meter").with_aliases(&["in", "inches"]),
+            UnitDefinition::new("kilogram", 1.0, "kilogram").with_aliases(&["kg"]),
+            UnitDefinition::new("gram", 0.001, "kilogram").with_aliases(&["g"]),
+            UnitDefinition::new("milligram", 0.001, "gram").with_aliases(&["mg"]),
+            UnitDefinition::new("tonne", 1000.0, "kilogram").with_aliases(&["t", "ton"]),
+            UnitDefinition::new("pound", 0.45359237, "kilogram").with_aliases(&["lb", "lbs"]),
+            UnitDefinition::new("ounce", 0.0625, "pound").with_aliases(&["oz"]),
+            UnitDefinition::new("second", 1.0, "second").with_aliases(&["s", "sec"]),
+            UnitDefinition::new("minute", 60.0, "second").with_aliases(&["min"]),
+            UnitDefinition::new("hour", 60.0, "minute").with_aliases(&["h", "hr"]),
+            UnitDefinition::new("day", 24.0, "hour").with_aliases(&["d"]),
+            UnitDefinition::new("week", 7.0, "day").with_aliases(&["wk"]),
+            UnitDefinition::new("liter", 1.0, "liter").with_aliases(&["l", "litre"]),
+            UnitDefinition::new("milliliter", 0.001, "liter").with_aliases(&["ml", "millilitre"]),
+            UnitDefinition::new("gallon", 3.785411784, "liter").with_aliases(&["gal"]),
+            UnitDefinition::new("meter per second", 3.6, "kilometer per hour")
+                .with_aliases(&["m/s"]),
+            UnitDefinition::new("kilometer per hour", 1.0, "kilometer per hour")
+                .with_aliases(&["km/h", "kph"]),
+            UnitDefinition::new("mile per hour", 1.609344, "kilometer per hour")
+                .with_aliases(&["mph"]),
+            UnitDefinition::new("byte", 1.0, "byte").with_aliases(&["b"]),
+            UnitDefinition::new("kilobyte", 1000.0, "byte").with_aliases(&["kb"]),
+            UnitDefinition::new("megabyte", 1000.0, "kilobyte").with_aliases(&["mb"]),
+            UnitDefinition::new("gigabyte", 1000.0, "megabyte").with_aliases(&["gb"]),
+            UnitDefinition::new("celsius", 1.0, "kelvin")
+                .with_aliases(&["°c", "c", "degree celsius"])
+                .with_offset(273.15),
+            UnitDefinition::new("celsius", 1.8, "fahrenheit").with_offset(32.0),
+            UnitDefinition::new("fahrenheit", 1.0, "fahrenheit").with_aliases(&[
+                "°f",
+                "f",
+                "degree fahrenheit",
+            ]),
+            UnitDefinition::new("kelvin", 1.0, "kelvin").with_aliases(&["k"]),
+        ])
+    }
+
+    pub fn with_definitions(
+        mut self,
+        definitions: impl IntoIterator<Item = UnitDefinition>,
+    ) -> Self {
+        for definition in definitions {
+            self.define(definition);
+        }
+        self
+    }
+
+    /// later definitions take over the aliases of earlier ones and replace any earlier
+    /// definition between the same two units, whichever way it was written. a name or unit
+    /// that is already known (in any case, or through an alias) stays the same unit, so
+    /// `USD` and `usd` can't end up as two units with no conversion between them
+    pub fn define(&mut self, definition: UnitDefinition) {
+        let index = self.definitions.len();
+        let name = self.canonical(&definition.name);
+        let unit = self.canonical(&definition.unit);
+
+        for alias in std::iter::once(&definition.name).chain(&definition.aliases) {
+            self.names.insert(alias.to_lowercase(), name.clone());
+        }
+        self.names
+            .insert(definition.unit.to_lowercase(), unit.clone());
+
+        // `1 meter = 1 meter` style definitions only register the names
+        if name != unit {
+            for (from, to) in [(&name, &unit), (&unit, &name)] {
+                if let Some(edges) = self.edges.get_mut(from) {
+                    edges.retain(|edge| &edge.to != to);
+                }
+            }
+            self.edges.entry(name.clone()).or_default().push(Edge {
+                to: unit.clone(),
+                definition: index,
+                inverse: false,
+            });
+            self.edges.entry(unit).or_default().push(Edge {
+                to: name,
+                definition: index,
+                inverse: true,
+            });
+        }
+
+        self.definitions.push(definition);
+    }
+
+    /// the unit `name` already stands for, or `name` itself for a new one
+    fn canonical(&self, name: &str) -> String {
+        self.names
+            .get(&name.to_lowercase())
+            .cloned()
+            .unwrap_or_else(|| name.to_string())
+    }
+
+    /// a JSON array of unit definitions
+    pub fn load(mut self, path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let definitions: Vec<UnitDefinition> =
+            serde_json::from_str(&std::fs::read_to_string(path.as_ref())?)?;
+
+        for definition in definitions {
+            anyhow::ensure!(
+                definition.factor.is_finite()
+                    && definition.factor != 0.0
+                    && definition.offset.is_finite(),
+                "invalid definition of `{}` in {}",
+                definition.name,
+                path.as_ref().display()
+            );
+            self.define(definition);
+        }
+
+        Ok(self)
+    }
+
+    /// the unit names, without aliases
+    pub fn units(&self) -> Vec<&str> {
+        let mut units: Vec<&str> = self.names.values().map(String::as_str).collect();
+        units.sort_unstable();
+        units.dedup();
+        units
+    }
+
+    /// resolves a name or alias, case-insensitively and ignoring a plural `s` (but not
+    /// on single letter units, `ms` isn't a plural of `m`)
+    pub fn resolve(&self, name: &str) -> Option<&str> {
+        let key = name.trim().to_lowercase();
+        self.names
+            .get(&key)
+            .or_else(|| {
+                let singular = key
+                    .strip_suffix('s')
+                    .filter(|key| key.chars().count() > 1)?;
+                self.names.get(singular)
+            })
+            .map(String::as_str)
+    }
+
+    /// the shortest chain of definitions going from `from` to `to`
+    fn path<'a>(&'a self, from: &'a str, to: &str) -> Option<Vec<Edge>> {
+        let mut previous: HashMap<&str, (&str, &Edge)> = HashMap::new();
+        let mut visited = HashSet::from([from]);
+        let mut queue = VecDeque::from([from]);
+
+        while let Some(unit) = queue.pop_front() {
+            if unit == to {
+                let mut path = Vec::new();
+                let mut current = to;
+                while let Some((before, edge)) = previous.get(current) {
+                    path.push((*edge).clone());
+                    current = before;
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            for edge in self.edges.get(unit).into_iter().flatten() {
+                if visited.insert(edge.to.as_str()) {
+                    previous.insert(edge.to.as_str(), (unit, edge));
+                    queue.push_back(edge.to.as_str());
+                }
+            }
+        }
+
+        None
+    }
+
+    /// converts `value` and returns it along with every hop taken and, for purely
+    /// proportional conversions, the rate (how many `to` one `from` is worth)
+    pub fn convert(
+        &self,
+        context: &MathContext,
+        value: Number,
+        from: &str,
+        to: &str,
+    ) -> Result<(Number, Option<Number>, Vec<ConversionStep>), MathError> {
+        let unknown = |name: &str, unit: &str| MathError::InvalidArgument {
+            name: name.to_string(),
+            reason: format!("unknown unit `{unit}`"),
+        };
+        let from = self.resolve(from).ok_or_else(|| unknown("from", from))?;
+        let to = self.resolve(to).ok_or_else(|| unknown("to", to))?;
+        let path = self
+            .path(from, to)
+            .ok_or_else(|| MathError::InvalidArgument {
+                name: "to".to_string(),
+                reason: format!("there is no known conversion from `{from}` to `{to}`"),
+            })?;
+
+        let mut value = value;
+        let mut rate = Some(context.number("rate", 1.0)?);
+        let mut steps = Vec::with_capacity(path.len());
+        let mut current = from;
+
+        for edge in &path {
+            let definition = &self.definitions[edge.definition];
+            let factor = context.number("factor", definition.factor)?;
+            let offset = context.number("offset", definition.offset)?;
+
+            value = if edge.inverse {
+                value
+                    .apply(BinaryOp::Subtract, offset)?
+                    .apply(BinaryOp::Divide, factor)?
+            } else {
+                value
+                    .apply(BinaryOp::Multiply, factor)?
+                    .apply(BinaryOp::Add, offset)?
+            };
+            rate = match rate {
+                Some(rate) if offset.is_zero() => Some(rate.apply(
+                    if edge.inverse {
+                        BinaryOp::Divide
+                    } else {
+                        BinaryOp::Multiply
+                    },
+                    factor,
+                )?),
+                _ => None,
+            };
+
+            steps.push(ConversionStep {
+                from: current.to_string(),
+                to: edge.to.clone(),
+                definition: describe(definition),
+            });
+            current = &edge.to;
+        }
+
+        Ok((value, rate, steps))
+    }
+}
+
+fn describe(definition: &UnitDefinition) -> String {
+    let UnitDefinition {
+        name,
+        factor,
+        unit,
+        offset,
+        ..
+    } = definition;
+
+    if *offset == 0.0 {
+        format!("1 {name} = {factor} {unit}")
+    } else if *offset < 0.0 {
+        format!("{unit} = {name} * {factor} - {}", -offset)
+    } else {
+        format!("{unit} = {name} * {factor} + {offset}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(table: &ConversionTable, value: f64, from: &str, to: &str) -> f64 {
+        let context = MathContext::decimal();
+        let value = context.number("value", value).unwrap();
+        let (value, _, _) = table.convert(&context, value, from, to).unwrap();
+        value.to_f64()
+    }
+
+    #[test]
+    fn chains_units_written_in_different_cases() {
+        let table = ConversionTable::new().with_definitions([
+            UnitDefinition::parse("1 flurbo = 10 USD").unwrap(),
+            UnitDefinition::parse("1 EUR = 1.08 usd").unwrap(),
+        ]);
+
+        assert_eq!(table.units(), ["EUR", "USD", "flurbo"]);
+        assert!((convert(&table, 5.4, "flurbos", "eur") - 50.0).abs() < 1e-9);
+
+        let context = MathContext::decimal();
+        let value = context.number("value", 1.0).unwrap();
+        let (_, rate, steps) = table.convert(&context, value, "Flurbo", "EUR").unwrap();
+        assert_eq!(steps.len(), 2);
+        assert_eq!((steps[0].to.as_str(), steps[1].to.as_str()), ("USD", "EUR"));
+        assert!(rate.is_some());
+    }
+
+    #[test]
+    fn aliases_stay_the_same_unit() {
+        let table =
+            ConversionTable::builtin().with_definitions([UnitDefinition::new("league", 3.0, "MI")]);
+
+        assert_eq!(table.resolve("leagues"), Some("league"));
+        assert!((convert(&table, 1.0, "league", "km") - 4.828032).abs() < 1e-9);
+    }
+
+    #[test]
+    fn resolves_plurals_but_not_single_letters() {
+        let table = ConversionTable::builtin();
+
+        assert_eq!(table.resolve(" Meters "), Some("meter"));
+        assert_eq!(table.resolve("feet"), Some("foot"));
+        assert_eq!(table.resolve("ms"), None);
+    }
+
+    #[test]
+    fn converts_temperatures_with_offsets() {
+        let table = ConversionTable::builtin();
+        let context = MathContext::decimal();
+        let value = context.number("value", 100.0).unwrap();
+        let (value, rate, _) = table.convert(&context, value, "°C", "F").unwrap();
+
+        assert!((value.to_f64() - 212.0).abs() < 1e-9);
+        assert!(rate.is_none());
+        assert!((convert(&table, 212.0, "fahrenheit", "kelvin") - 373.15).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rejects_unknown_and_unconnected_units() {
+        let table = ConversionTable::builtin();
+        let context = MathContext::decimal();
+        let value = context.number("value", 1.0).unwrap();
+
+        assert!(table.convert(&context, value, "parsec", "meter").is_err());
+        assert!(table.convert(&context, value, "meter", "kilogram").is_err());
+    }
+
+    #[test]
+    fn extracts_tags() {
+        let definitions = UnitDefinition::extract_tags(
+            "worth 10 USD [convert: 1 flurbo = 10 USD], or [convert: nonsense] and \
+             [convert: 4 glarbs = 1,000 grams",
+        );
+
+        assert_eq!(definitions, [UnitDefinition::new("flurbo", 10.0, "USD")]);
+        assert_eq!(
+            UnitDefinition::parse("4 glarbs = 1,000 grams"),
+            Some(UnitDefinition::new("glarbs", 250.0, "grams"))
+        );
+        assert_eq!(UnitDefinition::parse("0 x = 1 y"), None);
+    }
+
+    #[test]
+    fn redefining_a_rate_replaces_it() {
+        let table = ConversionTable::new().with_definitions([
+            UnitDefinition::parse("1 flurbo = 10 USD").unwrap(),
+            UnitDefinition::parse("1 Flurbo = 12 usd").unwrap(),
+        ]);
+        assert!((convert(&table, 2.0, "flurbo", "USD") - 24.0).abs() < 1e-9);
+        assert!((convert(&table, 24.0, "USD", "flurbo") - 2.0).abs() < 1e-9);
+
+        // written the other way around
+        let table = table.with_definitions([UnitDefinition::parse("1 USD = 0.2 flurbo").unwrap()]);
+        assert!((convert(&table, 2.0, "flurbo", "USD") - 10.0).abs() < 1e-9);
+        assert!((convert(&table, 10.0, "USD", "flurbo") - 2.0).abs() < 1e-9);
+    }
+}
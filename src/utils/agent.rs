@@ -1,42 +1,390 @@
-use std::ops::{Deref, DerefMut};
+use std::{
+    collections::HashMap,
+    ops::{Deref, DerefMut},
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
 
 use rig::{
     OneOrMany,
     agent::Agent,
-    completion::{Completion, PromptError},
+    completion::{Completion, CompletionError, PromptError, ToolDefinition},
     message::{AssistantContent, Message, ToolCall, ToolFunction, ToolResultContent, UserContent},
+    tool::{Tool, ToolSetError},
+    vector_store::VectorStoreError,
+};
+use serde_json::{Value, json};
+use tracing::{Instrument, Span, field::Empty, info_span};
+
+use super::{
+    approval::{ApprovalDecision, PendingToolCall},
+    index::ScoreIds,
+    outcome::{ToolInvocation, TurnEvent, TurnOutcome},
+    provenance::{DocumentOrigin, Provenance},
+    session::{SessionHandle, SessionSnapshot},
+    usage::{BudgetExceeded, ReportsUsage, Usage, UsageTracker},
+    validate::{Validated, validate},
 };
-use serde_json::json;
+use crate::tools::{Ledger, Lookup, MathError, ToolCache};
+
+#[derive(Debug, thiserror::Error)]
+pub enum AgentError {
+    #[error(transparent)]
+    Prompt(#[from] PromptError),
+    #[error("Vector store error: {0}")]
+    VectorStore(#[from] VectorStoreError),
+    #[error(transparent)]
+    BudgetExceeded(#[from] BudgetExceeded),
+    #[error("Could not restore the ledger: {0}")]
+    Ledger(#[from] MathError),
+    #[error("A `{0}` call is waiting for approval, resume the turn first")]
+    ApprovalPending(String),
+    #[error("There is no tool call waiting for approval")]
+    NoPendingApproval,
+    #[error("There is no profile `{0}`")]
+    UnknownProfile(String),
+}
+
+impl From<CompletionError> for AgentError {
+    fn from(err: CompletionError) -> Self {
+        Self::Prompt(err.into())
+    }
+}
+
+impl From<ToolSetError> for AgentError {
+    fn from(err: ToolSetError) -> Self {
+        Self::Prompt(err.into())
+    }
+}
+
+type ApprovalCondition = Box<dyn Fn(&Value) -> bool + Send + Sync>;
+type EventHandler = Box<dyn Fn(&TurnEvent) + Send + Sync>;
 
 pub struct MultiTurnAgent<M: rig::completion::CompletionModel + Send + Sync> {
+    /// the agent of the active profile
     agent: Agent<M>,
+    profile: String,
+    /// the other profiles, by name, swapped with `agent` when one is selected
+    profiles: HashMap<String, Agent<M>>,
     chat_history: Vec<Message>,
+    /// the same index (and sample size) given to `dynamic_context`, queried again so we
+    /// know which documents the agent was handed
+    context_index: Option<(usize, Box<dyn ScoreIds + Send + Sync>)>,
+    /// minimum similarity for a document to be cited by an answer sentence, `None`
+    /// disables the citation pass
+    citation_threshold: Option<f64>,
+    /// session-wide usage, along with the model name it is priced as
+    usage_tracker: Option<(UsageTracker, String)>,
+    /// session state shared with the agent's `ledger` tool
+    ledger: Option<Ledger>,
+    /// memoized tool results, only valid for the current session
+    tool_cache: Option<ToolCache>,
+    /// tools that pause the turn before being called, if their condition holds for the
+    /// arguments
+    approvals: HashMap<String, ApprovalCondition>,
+    /// the turn waiting for `resume`, along with the call it paused on
+    paused: Option<(TurnState, PendingToolCall)>,
+    event_handler: Option<EventHandler>,
+    /// parameters schema of every tool, by name, used to validate their arguments
+    tool_schemas: HashMap<String, Value>,
+    session_id: String,
+    /// kept in sync with `session_id`, for tools outside the agent
+    session_handle: Option<SessionHandle>,
+    /// parent of every turn's span, replaced whenever the history is cleared
+    session_span: Span,
 }
 
-impl<M: rig::completion::CompletionModel> MultiTurnAgent<M> {
-    pub fn new(agent: Agent<M>) -> Self {
+impl<M> MultiTurnAgent<M>
+where
+    M: rig::completion::CompletionModel,
+    M::Response: ReportsUsage,
+{
+    /// starts out with `agent` as the active profile `name`, see `with_profile`
+    pub fn from_profile(name: impl Into<String>, agent: Agent<M>) -> Self {
+        let session_id = new_session_id();
         Self {
             agent,
+            profile: name.into(),
+            profiles: HashMap::new(),
             chat_history: Vec::new(),
+            context_index: None,
+            citation_threshold: None,
+            usage_tracker: None,
+            ledger: None,
+            tool_cache: None,
+            approvals: HashMap::new(),
+            paused: None,
+            event_handler: None,
+            tool_schemas: HashMap::new(),
+            session_handle: None,
+            session_span: session_span(&session_id),
+            session_id,
+        }
+    }
+
+    /// adds another agent, e.g. with its own preamble and tools, that sessions can switch
+    /// to with `set_profile`. everything else (history, ledger, approvals, indexes, etc.)
+    /// is shared between profiles, so they should be built over the same stores
+    pub fn with_profile(mut self, name: impl Into<String>, agent: Agent<M>) -> Self {
+        let name = name.into();
+        if name == self.profile {
+            self.agent = agent;
+        } else {
+            self.profiles.insert(name, agent);
+        }
+        self
+    }
+
+    /// should mirror the arguments given to `dynamic_context` when building the agent
+    pub fn with_context_index(
+        mut self,
+        sample: usize,
+        index: impl ScoreIds + Send + Sync + 'static,
+    ) -> Self {
+        self.context_index = Some((sample, Box::new(index)));
+        self
+    }
+
+    /// maps every sentence of the final answer to the retrieved documents supporting it,
+    /// requires `with_context_index`
+    pub fn with_citations(mut self, threshold: f64) -> Self {
+        self.citation_threshold = Some(threshold);
+        self
+    }
+
+    /// records every completion's usage under `model_name`, and stops the loop with
+    /// `AgentError::BudgetExceeded` as soon as the tracker's budget is exceeded
+    pub fn with_usage_tracker(
+        mut self,
+        tracker: UsageTracker,
+        model_name: impl Into<String>,
+    ) -> Self {
+        self.usage_tracker = Some((tracker, model_name.into()));
+        self
+    }
+
+    /// should be a clone of the `Ledger` given to the agent as a tool, so it is saved
+    /// with the session and cleared along with the history
+    pub fn with_ledger(mut self, ledger: Ledger) -> Self {
+        self.ledger = Some(ledger);
+        self
+    }
+
+    /// should be the `ToolCache` given to the agent's memoized tools, so results don't
+    /// outlive the session they were computed in
+    pub fn with_tool_cache(mut self, cache: ToolCache) -> Self {
+        self.tool_cache = Some(cache);
+        self
+    }
+
+    /// pauses the turn before calling `tool` whenever `condition` holds for its (repaired)
+    /// arguments, e.g. `|_| true` for every call. the outcome then carries the
+    /// `pending_approval`, and the turn goes on with `resume`
+    pub fn with_approval(
+        mut self,
+        tool: impl Into<String>,
+        condition: impl Fn(&Value) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.approvals.insert(tool.into(), Box::new(condition));
+        self
+    }
+
+    /// tools given a clone of `handle` (e.g. `remember`) always see the current session id
+    pub fn with_session_handle(mut self, handle: SessionHandle) -> Self {
+        handle.set(&self.session_id);
+        self.session_handle = Some(handle);
+        self
+    }
+
+    /// called with every `TurnEvent` as it happens, e.g. to show tool calls while the turn
+    /// is still running
+    pub fn with_event_handler(
+        mut self,
+        handler: impl Fn(&TurnEvent) + Send + Sync + 'static,
+    ) -> Self {
+        self.event_handler = Some(Box::new(handler));
+        self
+    }
+
+    pub fn chat_history(&self) -> &[Message] {
+        &self.chat_history
+    }
+
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// the name of the active profile
+    pub fn profile(&self) -> &str {
+        &self.profile
+    }
+
+    /// the names of every profile, sorted
+    pub fn profiles(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self
+            .profiles
+            .keys()
+            .map(String::as_str)
+            .chain([self.profile.as_str()])
+            .collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// switches to the profile `name`, starting a new session unless it is already the
+    /// active one
+    pub async fn set_profile(&mut self, name: &str) -> Result<(), AgentError> {
+        if name == self.profile {
+            return Ok(());
         }
+        self.switch_profile(name)?;
+        self.clear_history().await;
+        Ok(())
     }
 
     pub async fn multi_turn_prompt(
         &mut self,
         prompt: impl Into<Message> + Send,
-    ) -> Result<String, PromptError> {
-        let mut current_prompt: Message = prompt.into();
-        loop {
-            // println!("Current Prompt: {:?}\n", current_prompt);
-            // for history in self.chat_history.iter() {
-            //     println!("Chat History: {:?}\n", history);
-            // }
-            let resp = self
-                .agent
-                .completion(current_prompt.clone(), self.chat_history.clone())
-                .await?
-                .send()
+    ) -> Result<TurnOutcome, AgentError> {
+        if let Some((_, call)) = &self.paused {
+            return Err(AgentError::ApprovalPending(call.name.clone()));
+        }
+
+        let prompt: Message = prompt.into();
+        let span = info_span!(
+            parent: &self.session_span,
+            "turn",
+            prompt = %prompt_text(&prompt).unwrap_or_default(),
+            turns = Empty,
+            tool_calls = Empty,
+        );
+
+        self.run_turn(prompt).instrument(span).await
+    }
+
+    /// continues the paused turn, calling the pending tool (with the edited arguments, if
+    /// any) or reporting the denial to the model. the turn may pause again on a later call
+    pub async fn resume(&mut self, decision: ApprovalDecision) -> Result<TurnOutcome, AgentError> {
+        let Some((mut turn, call)) = self.paused.take() else {
+            return Err(AgentError::NoPendingApproval);
+        };
+        let span = info_span!(
+            parent: &self.session_span,
+            "turn",
+            resumed = %call.name,
+            turns = Empty,
+            tool_calls = Empty,
+        );
+
+        async {
+            let validated = match &decision {
+                ApprovalDecision::Approve => Ok(Validated {
+                    arguments: call.arguments,
+                    repairs: call.repairs,
+                }),
+                ApprovalDecision::Deny { reason } => {
+                    Err(ApprovalDecision::denial(reason.as_deref()))
+                }
+                ApprovalDecision::Edit { arguments } => {
+                    self.validate_arguments(&call.name, arguments).await
+                }
+            };
+            // an edit replaces what the model sent, the original stays in the history
+            let arguments = match &decision {
+                ApprovalDecision::Edit { arguments } => arguments.clone(),
+                _ => call.original_arguments,
+            };
+            let prompt = self
+                .call_tool(
+                    call.id,
+                    call.name,
+                    arguments,
+                    validated,
+                    Some(decision),
+                    &mut turn,
+                )
+                .await;
+            self.run_loop(prompt, turn).await
+        }
+        .instrument(span)
+        .await
+    }
+
+    async fn run_turn(&mut self, current_prompt: Message) -> Result<TurnOutcome, AgentError> {
+        let mut turn = TurnState::new(self.chat_history.len());
+
+        if let (Some((sample, index)), Some(text)) =
+            (&self.context_index, prompt_text(&current_prompt))
+        {
+            let span = info_span!("vector_search", query = %text, n = sample, results = Empty);
+            let results = index
+                .top_n_ids(&text, *sample)
+                .instrument(span.clone())
                 .await?;
+            span.record("results", results.len());
+
+            for (score, id) in results {
+                turn.provenance
+                    .record_document(id, score, DocumentOrigin::DynamicContext);
+            }
+        }
+
+        self.run_loop(current_prompt, turn).await
+    }
+
+    /// a turn failing halfway (over budget, a failed request, etc.) is dropped from the
+    /// history, it could end on a tool call without its result, which providers reject on
+    /// the next prompt
+    async fn run_loop(
+        &mut self,
+        current_prompt: Message,
+        turn: TurnState,
+    ) -> Result<TurnOutcome, AgentError> {
+        let history_start = turn.history_start;
+        let outcome = self.run_completions(current_prompt, turn).await;
+        if outcome.is_err() {
+            self.chat_history.truncate(history_start);
+        }
+        outcome
+    }
+
+    async fn run_completions(
+        &mut self,
+        mut current_prompt: Message,
+        mut turn: TurnState,
+    ) -> Result<TurnOutcome, AgentError> {
+        loop {
+            if let Some((tracker, _)) = &self.usage_tracker {
+                tracker.check_budget()?;
+            }
+
+            turn.turns += 1;
+            let span = info_span!(
+                "completion",
+                turn = turn.turns,
+                latency_ms = Empty,
+                prompt_tokens = Empty,
+                completion_tokens = Empty,
+            );
+            let started = Instant::now();
+            let resp = async {
+                self.agent
+                    .completion(current_prompt.clone(), self.chat_history.clone())
+                    .await?
+                    .send()
+                    .await
+            }
+            .instrument(span.clone())
+            .await?;
+            span.record("latency_ms", started.elapsed().as_millis() as u64);
+
+            if let Some(turn_usage) = resp.raw_response.usage() {
+                span.record("prompt_tokens", turn_usage.prompt_tokens);
+                span.record("completion_tokens", turn_usage.completion_tokens);
+                *turn.usage.get_or_insert_default() += turn_usage;
+                if let Some((tracker, model_name)) = &self.usage_tracker {
+                    tracker.record(model_name, turn_usage);
+                }
+            }
 
             self.chat_history.push(current_prompt.clone());
 
@@ -47,15 +395,22 @@ impl<M: rig::completion::CompletionModel> MultiTurnAgent<M> {
                 match content {
                     AssistantContent::Text(text) => {
                         if resp_choice_len > 1 {
-                            println!("Intermediate Response (CoT): {:?}", text.text);
+                            tracing::info!(text = %text.text, "intermediate response (CoT)");
+                        }
+                        self.emit(TurnEvent::Text(&text.text));
+                        if let Some(previous) = final_text.replace(text.text.clone()) {
+                            turn.intermediate_texts.push(previous);
                         }
-                        final_text = Some(text.text.clone());
                         let response_message = Message::Assistant {
                             content: OneOrMany::one(AssistantContent::text(&text.text)),
                         };
                         self.chat_history.push(response_message);
                     }
                     AssistantContent::ToolCall(content) => {
+                        if let Some(previous) = final_text.take() {
+                            turn.intermediate_texts.push(previous);
+                        }
+
                         let tool_call_msg = AssistantContent::ToolCall(content.clone());
 
                         self.chat_history.push(Message::Assistant {
@@ -67,33 +422,283 @@ impl<M: rig::completion::CompletionModel> MultiTurnAgent<M> {
                             function: ToolFunction { name, arguments },
                         } = content;
 
-                        let tool_result: ToolResult = (
-                            name.clone(),
-                            self.agent.tools.call(&name, arguments.to_string()).await?,
-                        )
-                            .into();
-
-                        current_prompt = Message::User {
-                            content: OneOrMany::one(UserContent::tool_result(
-                                id,
-                                OneOrMany::one(tool_result.into()),
-                            )),
+                        // arguments are checked against the tool's schema first, so
+                        // harmless mistakes get repaired and the rest get a precise error
+                        // instead of an opaque deserialization failure
+                        let validated = match self.validate_arguments(&name, &arguments).await {
+                            Ok(validated) if self.requires_approval(&name, &validated) => {
+                                let call = PendingToolCall {
+                                    id,
+                                    name,
+                                    arguments: validated.arguments,
+                                    original_arguments: arguments,
+                                    repairs: validated.repairs,
+                                };
+                                return Ok(self.pause(turn, call));
+                            }
+                            validated => validated,
                         };
+                        current_prompt = self
+                            .call_tool(id, name, arguments, validated, None, &mut turn)
+                            .await;
 
-                        final_text = None;
                         break;
                     }
                 }
             }
 
             if let Some(text) = final_text {
-                return Ok(text);
+                if let (Some((_, index)), Some(threshold)) =
+                    (&self.context_index, self.citation_threshold)
+                {
+                    turn.provenance
+                        .cite(index.as_ref(), &text, threshold)
+                        .await?;
+                }
+                if let Some((tracker, _)) = &self.usage_tracker {
+                    tracker.record_turn(turn.usage.unwrap_or_default());
+                }
+
+                return Ok(turn.outcome(text, &self.chat_history, None));
+            }
+        }
+    }
+
+    /// calls the tool with the validated arguments, or hands the validation error (or
+    /// denial) back as its result, and returns the message carrying that result
+    async fn call_tool(
+        &mut self,
+        id: String,
+        name: String,
+        arguments: Value,
+        validated: Result<Validated, String>,
+        approval: Option<ApprovalDecision>,
+        turn: &mut TurnState,
+    ) -> Message {
+        let span = info_span!(
+            "tool_call",
+            tool = %name,
+            args = %arguments,
+            latency_ms = Empty,
+            result_size = Empty,
+            error = Empty,
+            repairs = Empty,
+        );
+        self.emit(TurnEvent::ToolCall {
+            name: &name,
+            arguments: &arguments,
+        });
+        let started = Instant::now();
+        // tool failures are handed back to the model instead of aborting the whole
+        // prompt, so it gets a chance to correct itself
+        let (result, repairs) = match validated {
+            Ok(Validated {
+                arguments: repaired,
+                repairs,
+            }) => {
+                if !repairs.is_empty() {
+                    span.record("repairs", repairs.join("; "));
+                }
+                let result = self
+                    .agent
+                    .tools
+                    .call(&name, repaired.to_string())
+                    .instrument(span.clone())
+                    .await
+                    .map_err(|err| err.to_string());
+                (result, repairs)
+            }
+            Err(err) => (Err(err), Vec::new()),
+        };
+        let duration = started.elapsed();
+
+        span.record("latency_ms", duration.as_millis() as u64);
+        match &result {
+            Ok(result) => span.record("result_size", result.len()),
+            Err(error) => span.record("error", error.as_str()),
+        };
+
+        let looked_up = result
+            .as_ref()
+            .ok()
+            .filter(|_| name == Lookup::NAME)
+            .and_then(|result| lookup_document(result));
+        if let Some((id, score)) = looked_up {
+            turn.provenance
+                .record_document(id, score, DocumentOrigin::Lookup);
+        }
+        turn.tool_calls.push(ToolInvocation {
+            name: name.clone(),
+            arguments,
+            result: result.clone().ok(),
+            error: result.clone().err(),
+            repairs,
+            approval,
+            duration,
+        });
+        if let Some(invocation) = turn.tool_calls.last() {
+            self.emit(TurnEvent::ToolResult(invocation));
+        }
+
+        let tool_result: ToolResult = (name, result).into();
+
+        Message::User {
+            content: OneOrMany::one(UserContent::tool_result(
+                id,
+                OneOrMany::one(tool_result.into()),
+            )),
+        }
+    }
+
+    async fn validate_arguments(
+        &mut self,
+        name: &str,
+        arguments: &Value,
+    ) -> Result<Validated, String> {
+        match self.tool_schema(name).await {
+            Some(schema) => {
+                validate(name, &schema, arguments.clone()).map_err(|err| err.to_string())
+            }
+            None => Ok(Validated {
+                arguments: arguments.clone(),
+                repairs: Vec::new(),
+            }),
+        }
+    }
+
+    fn requires_approval(&self, name: &str, validated: &Validated) -> bool {
+        self.approvals
+            .get(name)
+            .is_some_and(|condition| condition(&validated.arguments))
+    }
+
+    /// sets the turn aside until `resume`, the outcome so far is returned to the caller
+    fn pause(&mut self, turn: TurnState, call: PendingToolCall) -> TurnOutcome {
+        tracing::info!(tool = %call.name, args = %call.arguments, "waiting for approval");
+        let outcome = turn.outcome(String::new(), &self.chat_history, Some(call.clone()));
+        self.emit(TurnEvent::ApprovalNeeded(&call));
+        self.paused = Some((turn, call));
+        outcome
+    }
+
+    /// every tool the agent can call, static ones first
+    pub async fn tool_definitions(&self) -> Vec<ToolDefinition> {
+        let mut names = self.agent.static_tools.clone();
+        // dynamic tools are only offered by name, through the schemas they are embedded from.
+        // those only fail to build if a tool's context can't be serialized, which ours can
+        let schemas = self.agent.tools.schemas().unwrap_or_default();
+        for schema in schemas {
+            if !names.contains(&schema.name) {
+                names.push(schema.name);
             }
         }
+
+        let mut definitions = Vec::new();
+        for name in names {
+            if let Some(definition) = self.tool_definition(&name).await {
+                definitions.push(definition);
+            }
+        }
+        definitions
     }
 
+    /// the definition the tool `name` was registered with, as the model gets it
+    async fn tool_definition(&self, name: &str) -> Option<ToolDefinition> {
+        let tool = self.agent.tools.get(name)?;
+        Some(tool.definition(String::new()).await)
+    }
+
+    /// the parameters schema of the tool `name`, asked for once per profile
+    async fn tool_schema(&mut self, name: &str) -> Option<Value> {
+        if !self.tool_schemas.contains_key(name) {
+            let definition = self.tool_definition(name).await?;
+            self.tool_schemas
+                .insert(name.to_string(), definition.parameters);
+        }
+        self.tool_schemas.get(name).cloned()
+    }
+
+    /// also starts a new session, with an empty ledger and tool cache, dropping any turn
+    /// waiting for approval
     pub async fn clear_history(&mut self) {
         self.chat_history.clear();
+        self.paused = None;
+        if let Some(ledger) = &self.ledger {
+            ledger.clear();
+        }
+        if let Some(cache) = &self.tool_cache {
+            cache.clear();
+        }
+        self.start_session(new_session_id());
+    }
+
+    pub fn snapshot(&self) -> SessionSnapshot {
+        SessionSnapshot {
+            session_id: self.session_id.clone(),
+            profile: Some(self.profile.clone()),
+            chat_history: self.chat_history.clone(),
+            ledger: self
+                .ledger
+                .as_ref()
+                .map(Ledger::entries)
+                .unwrap_or_default(),
+        }
+    }
+
+    /// continues a saved session, replacing the current history and ledger and switching
+    /// back to its profile. a turn waiting for approval isn't part of the snapshot
+    pub fn restore(&mut self, snapshot: SessionSnapshot) -> Result<(), AgentError> {
+        // nothing is changed unless the whole snapshot can be restored
+        let unknown = snapshot
+            .profile
+            .as_ref()
+            .filter(|profile| **profile != self.profile && !self.profiles.contains_key(*profile));
+        if let Some(profile) = unknown {
+            return Err(AgentError::UnknownProfile(profile.clone()));
+        }
+        if let Some(ledger) = &self.ledger {
+            ledger.restore(snapshot.ledger)?;
+        }
+        if let Some(profile) = &snapshot.profile {
+            self.switch_profile(profile)?;
+        }
+        self.paused = None;
+        if let Some(cache) = &self.tool_cache {
+            cache.clear();
+        }
+        self.chat_history = snapshot.chat_history;
+        self.start_session(snapshot.session_id);
+        Ok(())
+    }
+
+    fn switch_profile(&mut self, name: &str) -> Result<(), AgentError> {
+        if name == self.profile {
+            return Ok(());
+        }
+        let agent = self
+            .profiles
+            .remove(name)
+            .ok_or_else(|| AgentError::UnknownProfile(name.to_string()))?;
+        let previous = std::mem::replace(&mut self.agent, agent);
+        let previous_name = std::mem::replace(&mut self.profile, name.to_string());
+        self.profiles.insert(previous_name, previous);
+        // profiles may give the same tool name different schemas
+        self.tool_schemas.clear();
+        Ok(())
+    }
+
+    fn emit(&self, event: TurnEvent) {
+        if let Some(handler) = &self.event_handler {
+            handler(&event);
+        }
+    }
+
+    fn start_session(&mut self, session_id: String) {
+        if let Some(handle) = &self.session_handle {
+            handle.set(&session_id);
+        }
+        self.session_span = session_span(&session_id);
+        self.session_id = session_id;
     }
 }
 impl<M: rig::completion::CompletionModel + Send + Sync> Deref for MultiTurnAgent<M> {
@@ -109,20 +714,176 @@ impl<M: rig::completion::CompletionModel + Send + Sync> DerefMut for MultiTurnAg
     }
 }
 
-pub struct ToolResult(String, String);
-impl From<(String, String)> for ToolResult {
-    fn from(value: (String, String)) -> Self {
+/// what a turn accumulated so far, kept aside while it waits for an approval
+struct TurnState {
+    /// where the turn's messages start in the chat history
+    history_start: usize,
+    provenance: Provenance,
+    intermediate_texts: Vec<String>,
+    tool_calls: Vec<ToolInvocation>,
+    usage: Option<Usage>,
+    turns: usize,
+}
+
+impl TurnState {
+    fn new(history_start: usize) -> Self {
+        Self {
+            history_start,
+            provenance: Provenance::default(),
+            intermediate_texts: Vec::new(),
+            tool_calls: Vec::new(),
+            usage: None,
+            turns: 0,
+        }
+    }
+
+    fn outcome(
+        &self,
+        text: String,
+        history: &[Message],
+        pending_approval: Option<PendingToolCall>,
+    ) -> TurnOutcome {
+        let span = Span::current();
+        span.record("turns", self.turns);
+        span.record("tool_calls", self.tool_calls.len());
+
+        TurnOutcome {
+            text,
+            intermediate_texts: self.intermediate_texts.clone(),
+            tool_calls: self.tool_calls.clone(),
+            turns: self.turns,
+            usage: self.usage,
+            messages: history[self.history_start..].to_vec(),
+            provenance: self.provenance.clone(),
+            pending_approval,
+        }
+    }
+}
+
+fn new_session_id() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    format!("{nanos:x}")
+}
+
+fn session_span(session_id: &str) -> Span {
+    info_span!(parent: None, "session", session_id)
+}
+
+fn prompt_text(message: &Message) -> Option<String> {
+    let Message::User { content } = message else {
+        return None;
+    };
+
+    let text = content
+        .iter()
+        .filter_map(|content| match content {
+            UserContent::Text(text) => Some(text.text.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    (!text.is_empty()).then_some(text)
+}
+
+/// pulls the document id and score out of a serialized `lookup` result
+fn lookup_document(result: &str) -> Option<(String, f64)> {
+    let value: Value = serde_json::from_str(result).ok()?;
+    let id = value.get("id")?.as_str()?.to_string();
+    let score = value
+        .get("score")
+        .and_then(Value::as_f64)
+        .unwrap_or_default();
+    Some((id, score))
+}
+
+pub struct ToolResult(String, Result<String, String>);
+impl From<(String, Result<String, String>)> for ToolResult {
+    fn from(value: (String, Result<String, String>)) -> Self {
         Self(value.0, value.1)
     }
 }
 impl From<ToolResult> for ToolResultContent {
     fn from(val: ToolResult) -> Self {
         ToolResultContent::text(
-            json!({
-                "name": val.0,
-                "result": val.1
-            })
+            match val.1 {
+                Ok(result) => json!({
+                    "name": val.0,
+                    "result": result
+                }),
+                Err(error) => json!({
+                    "name": val.0,
+                    "error": error
+                }),
+            }
             .to_string(),
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rig::providers::openai;
+
+    use super::*;
+    use crate::tools::MathContext;
+
+    /// never prompted, so the key is never sent anywhere
+    fn agent() -> Agent<openai::CompletionModel> {
+        openai::Client::new("unused").agent("gpt-4o-mini").build()
+    }
+
+    fn snapshot(profile: &str, ledger: Value) -> SessionSnapshot {
+        serde_json::from_value(json!({
+            "session_id": "saved",
+            "profile": profile,
+            "chat_history": [],
+            "ledger": ledger,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn failed_restore_keeps_the_session() {
+        let ledger = Ledger::new(MathContext::decimal());
+        let mut session = MultiTurnAgent::from_profile("assistant", agent())
+            .with_profile("calculator", agent())
+            .with_ledger(ledger.clone());
+        let credit =
+            json!([{ "asset": "flurbo", "operation": "credit", "amount": "5", "balance": "5" }]);
+        ledger
+            .restore(serde_json::from_value(credit).unwrap())
+            .unwrap();
+        session.chat_history.push(Message::user("I have 5 flurbos"));
+        let session_id = session.session_id().to_string();
+
+        // a debit the balance can't cover
+        let overdrawn = snapshot(
+            "calculator",
+            json!([{ "asset": "gem", "operation": "debit", "amount": "1", "balance": "-1" }]),
+        );
+        assert!(matches!(
+            session.restore(overdrawn),
+            Err(AgentError::Ledger(_))
+        ));
+        assert!(matches!(
+            session.restore(snapshot("accountant", json!([]))),
+            Err(AgentError::UnknownProfile(_))
+        ));
+
+        assert_eq!(session.profile(), "assistant");
+        assert_eq!(session.session_id(), session_id);
+        assert_eq!(session.chat_history().len(), 1);
+        assert_eq!(ledger.entries().len(), 1);
+        assert_eq!(ledger.entries()[0].asset, "flurbo");
+
+        session.restore(snapshot("calculator", json!([]))).unwrap();
+        assert_eq!(session.profile(), "calculator");
+        assert_eq!(session.session_id(), "saved");
+        assert!(session.chat_history().is_empty());
+        assert!(ledger.entries().is_empty());
+    }
+}
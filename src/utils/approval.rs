@@ -0,0 +1,140 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// a tool call the agent paused on, waiting for `MultiTurnAgent::resume`
+#[derive(Clone, Debug, Serialize)]
+pub struct PendingToolCall {
+    /// the provider's id of the call, the result is sent back under it
+    pub id: String,
+    pub name: String,
+    /// what the tool will be called with once approved, after repairs
+    pub arguments: Value,
+    /// the arguments as the model sent them
+    pub original_arguments: Value,
+    pub repairs: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "decision", rename_all = "snake_case")]
+pub enum ApprovalDecision {
+    Approve,
+    /// the model gets the denial (and its reason) as the tool's result, so it can tell
+    /// the user or try something else
+    Deny {
+        reason: Option<String>,
+    },
+    /// calls the tool with these arguments instead, validated like the model's
+    Edit {
+        arguments: Value,
+    },
+}
+
+impl ApprovalDecision {
+    pub fn deny(reason: impl Into<String>) -> Self {
+        Self::Deny {
+            reason: Some(reason.into()),
+        }
+    }
+
+    /// the error handed to the model for a denied call
+    pub(super) fn denial(reason: Option<&str>) -> String {
+        match reason {
+            Some(reason) => format!("The user denied this call: {reason}"),
+            None => "The user denied this call".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ParseDecisionError {
+    #[error("Invalid arguments: {0}")]
+    Arguments(#[from] serde_json::Error),
+    #[error("Expected y, n (optionally followed by a reason) or JSON arguments, got `{0}`")]
+    Unknown(String),
+}
+
+/// reads a decision typed by the user: "y" approves the call, "n" denies it, with whatever
+/// follows as the reason ("n too much"), and a JSON object replaces its arguments.
+/// anything else is an error rather than a denial, so a typo can be answered again
+impl std::str::FromStr for ApprovalDecision {
+    type Err = ParseDecisionError;
+
+    fn from_str(answer: &str) -> Result<Self, Self::Err> {
+        let answer = answer.trim();
+        if answer.starts_with('{') {
+            return Ok(Self::Edit {
+                arguments: serde_json::from_str(answer)?,
+            });
+        }
+
+        let (word, reason) = match answer.split_once(char::is_whitespace) {
+            Some((word, reason)) => (word, Some(reason.trim())),
+            None => (answer, None),
+        };
+        match (word.to_lowercase().as_str(), reason) {
+            ("y" | "yes", None) => Ok(Self::Approve),
+            ("n" | "no", Some(reason)) => Ok(Self::deny(reason)),
+            ("n" | "no", None) => Ok(Self::Deny { reason: None }),
+            _ => Err(ParseDecisionError::Unknown(answer.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(answer: &str) -> Result<ApprovalDecision, ParseDecisionError> {
+        answer.parse()
+    }
+
+    #[test]
+    fn approves_in_any_case() {
+        for answer in ["y", "yes", "Y", " YES \n", "Yes"] {
+            assert!(
+                matches!(parse(answer), Ok(ApprovalDecision::Approve)),
+                "{answer}"
+            );
+        }
+    }
+
+    #[test]
+    fn denies_with_an_optional_reason() {
+        assert!(matches!(
+            parse("n"),
+            Ok(ApprovalDecision::Deny { reason: None })
+        ));
+        assert!(matches!(
+            parse(" No \n"),
+            Ok(ApprovalDecision::Deny { reason: None })
+        ));
+        match parse("N   too much, ask first \n") {
+            Ok(ApprovalDecision::Deny { reason }) => {
+                assert_eq!(reason.as_deref(), Some("too much, ask first"))
+            }
+            other => panic!("expected a denial, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn json_replaces_the_arguments() {
+        match parse(r#"  {"action": "credit", "amount": 3} "#) {
+            Ok(ApprovalDecision::Edit { arguments }) => assert_eq!(arguments["amount"], 3),
+            other => panic!("expected an edit, got {other:?}"),
+        }
+        assert!(matches!(
+            parse(r#"{"action": "#),
+            Err(ParseDecisionError::Arguments(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_anything_else() {
+        for answer in ["", "  ", "yse", "ok", "yes please", "maybe later", "[1, 2]"] {
+            assert!(
+                matches!(parse(answer), Err(ParseDecisionError::Unknown(_))),
+                "{answer:?}"
+            );
+        }
+    }
+}
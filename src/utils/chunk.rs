@@ -0,0 +1,434 @@
+use std::path::Path;
+
+use rig::Embed;
+use serde::{Deserialize, Serialize};
+
+/// a piece of a long-form source (manual, markdown file, etc.), small enough to be
+/// embedded on its own but still carrying where it came from so it can be cited.
+#[derive(Embed, Deserialize, Serialize, Clone, Debug, Eq, PartialEq, Default)]
+pub struct Chunk {
+    pub id: String,
+    pub source: String,
+    /// the markdown headings enclosing this chunk, outermost first
+    pub heading_path: Vec<String>,
+    /// byte offsets of `text` inside the original source
+    pub start: usize,
+    pub end: usize,
+    #[embed]
+    pub text: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChunkStrategy {
+    /// windows of `size` bytes (snapped to char boundaries), each one repeating the
+    /// last `overlap` bytes of the previous window
+    FixedSize { size: usize, overlap: usize },
+    /// groups whole sentences up to `max_bytes` of UTF-8 (like every size and offset
+    /// here), repeating the last `overlap` sentences of the previous chunk
+    Sentence { max_bytes: usize, overlap: usize },
+    /// splits on markdown headings first, then falls back to sentence chunking for
+    /// sections longer than `max_bytes`
+    Markdown { max_bytes: usize, overlap: usize },
+}
+
+impl Default for ChunkStrategy {
+    fn default() -> Self {
+        Self::Markdown {
+            max_bytes: 1000,
+            overlap: 1,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Chunker {
+    strategy: ChunkStrategy,
+}
+
+impl Chunker {
+    pub fn new(strategy: ChunkStrategy) -> Self {
+        Self { strategy }
+    }
+
+    /// picks the markdown strategy for `.md` / `.markdown` files and the sentence
+    /// strategy for plain text (`.txt` or no extension), `overlap` being in sentences.
+    /// sentences mean nothing in anything else (code, logs, CSV, etc.), which is cut in
+    /// fixed windows of `max_bytes` overlapping by a tenth of that
+    pub fn for_path(path: impl AsRef<Path>, max_bytes: usize, overlap: usize) -> Self {
+        let extension = path
+            .as_ref()
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_lowercase);
+
+        Self::new(match extension.as_deref() {
+            Some("md" | "markdown") => ChunkStrategy::Markdown { max_bytes, overlap },
+            None | Some("txt" | "text") => ChunkStrategy::Sentence { max_bytes, overlap },
+            Some(_) => ChunkStrategy::FixedSize {
+                size: max_bytes,
+                overlap: max_bytes / 10,
+            },
+        })
+    }
+
+    pub fn chunk_file(&self, path: impl AsRef<Path>) -> anyhow::Result<Vec<Chunk>> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        Ok(self.chunk(&path.display().to_string(), &text))
+    }
+
+    pub fn chunk(&self, source: &str, text: &str) -> Vec<Chunk> {
+        let spans = match self.strategy {
+            ChunkStrategy::FixedSize { size, overlap } => fixed_size(text, 0, size, overlap)
+                .into_iter()
+                .map(|span| (Vec::new(), span))
+                .collect(),
+            ChunkStrategy::Sentence { max_bytes, overlap } => {
+                sentences(text, 0, max_bytes, overlap)
+                    .into_iter()
+                    .map(|span| (Vec::new(), span))
+                    .collect()
+            }
+            ChunkStrategy::Markdown { max_bytes, overlap } => markdown(text, max_bytes, overlap),
+        };
+
+        spans
+            .into_iter()
+            .filter_map(|(heading_path, (start, end))| {
+                let (start, end) = trim_span(text, start, end)?;
+                Some((heading_path, start, end))
+            })
+            .enumerate()
+            .map(|(i, (heading_path, start, end))| Chunk {
+                id: format!("{source}#{i}"),
+                source: source.to_string(),
+                heading_path,
+                start,
+                end,
+                text: text[start..end].to_string(),
+            })
+            .collect()
+    }
+}
+
+type Span = (usize, usize);
+
+/// shrinks a span so it doesn't start or end with whitespace, dropping it if
+/// nothing is left
+fn trim_span(text: &str, start: usize, end: usize) -> Option<Span> {
+    let slice = &text[start..end];
+    let trimmed_start = slice.len() - slice.trim_start().len();
+    let trimmed = slice.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+    let start = start + trimmed_start;
+    Some((start, start + trimmed.len()))
+}
+
+fn floor_char_boundary(text: &str, mut index: usize) -> usize {
+    index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+/// `offset` is added to every span, so this can work on a slice of a bigger text
+fn fixed_size(text: &str, offset: usize, size: usize, overlap: usize) -> Vec<Span> {
+    let size = size.max(1);
+    let overlap = overlap.min(size - 1);
+    let mut spans = Vec::new();
+    let mut start = 0;
+
+    while start < text.len() {
+        let mut end = floor_char_boundary(text, start + size);
+        if end <= start {
+            // a single char wider than `size`, take it whole
+            end = text[start..]
+                .char_indices()
+                .nth(1)
+                .map_or(text.len(), |(i, _)| start + i);
+        }
+        spans.push((offset + start, offset + end));
+
+        if end == text.len() {
+            break;
+        }
+        let next = floor_char_boundary(text, end - overlap);
+        start = if next > start { next } else { end };
+    }
+
+    spans
+}
+
+/// sentence boundaries are `.`, `!` or `?` followed by whitespace, or a blank line
+pub(super) fn sentence_spans(text: &str) -> Vec<Span> {
+    let mut spans = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        let next = chars.peek().map(|&(_, next)| next);
+        let end = match (c, next) {
+            ('.' | '!' | '?', Some(next)) if next.is_whitespace() => i + c.len_utf8(),
+            ('\n', Some('\n')) => i,
+            _ => continue,
+        };
+        if text[start..end].trim().is_empty() {
+            start = end;
+            continue;
+        }
+        spans.push((start, end));
+        start = end;
+    }
+
+    if !text[start..].trim().is_empty() {
+        spans.push((start, text.len()));
+    }
+
+    spans
+}
+
+fn sentences(text: &str, offset: usize, max_bytes: usize, overlap: usize) -> Vec<Span> {
+    let sentences = sentence_spans(text);
+    let mut spans = Vec::new();
+    let mut first = 0;
+    // index of the first sentence that wasn't part of any chunk yet
+    let mut fresh = 0;
+
+    while first < sentences.len() {
+        let mut start = sentences[first].0;
+        let mut last = first;
+        while last + 1 < sentences.len() && sentences[last + 1].1 - start <= max_bytes {
+            last += 1;
+        }
+        if last < fresh {
+            // the overlap alone filled the chunk, drop it instead of repeating it
+            first = fresh;
+            start = sentences[first].0;
+            last = first;
+            while last + 1 < sentences.len() && sentences[last + 1].1 - start <= max_bytes {
+                last += 1;
+            }
+        }
+        fresh = last + 1;
+
+        let end = sentences[last].1;
+        if end - start > max_bytes {
+            // one sentence on its own is already too long, cut it into fixed windows
+            spans.extend(fixed_size(&text[start..end], offset + start, max_bytes, 0));
+        } else {
+            spans.push((offset + start, offset + end));
+        }
+
+        if last + 1 == sentences.len() {
+            break;
+        }
+        // step back `overlap` sentences, but always move forward by at least one
+        first = (last + 1).saturating_sub(overlap).max(first + 1);
+    }
+
+    spans
+}
+
+/// returns the heading level and title if `line` is an ATX heading (`## Title`)
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let trimmed = line.trim_start();
+    let level = trimmed.chars().take_while(|&c| c == '#').count();
+    if !(1..=6).contains(&level) {
+        return None;
+    }
+    let rest = &trimmed[level..];
+    if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    Some((level, rest.trim().trim_end_matches('#').trim()))
+}
+
+fn markdown(text: &str, max_bytes: usize, overlap: usize) -> Vec<(Vec<String>, Span)> {
+    // (heading path, section start, section end)
+    let mut sections: Vec<(Vec<String>, usize, usize)> = Vec::new();
+    let mut path: Vec<(usize, String)> = Vec::new();
+    let mut section_start = 0;
+    let mut in_fence = false;
+    let mut offset = 0;
+
+    let current_path = |path: &[(usize, String)]| -> Vec<String> {
+        path.iter().map(|(_, title)| title.clone()).collect()
+    };
+
+    for line in text.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+
+        if line.trim_start().starts_with("```") || line.trim_start().starts_with("~~~") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+        let Some((level, title)) = heading(line) else {
+            continue;
+        };
+
+        sections.push((current_path(&path), section_start, line_start));
+        section_start = line_start;
+
+        while path.last().is_some_and(|(last, _)| *last >= level) {
+            path.pop();
+        }
+        path.push((level, title.to_string()));
+    }
+    sections.push((current_path(&path), section_start, text.len()));
+
+    sections
+        .into_iter()
+        .flat_map(|(heading_path, start, end)| {
+            let section = &text[start..end];
+            let spans = if section.len() <= max_bytes {
+                vec![(start, end)]
+            } else {
+                sentences(section, start, max_bytes, overlap)
+            };
+            spans
+                .into_iter()
+                .map(move |span| (heading_path.clone(), span))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(strategy: ChunkStrategy, text: &str) -> Vec<Chunk> {
+        let chunks = Chunker::new(strategy).chunk("doc.md", text);
+        for chunk in &chunks {
+            assert_eq!(chunk.text, text[chunk.start..chunk.end]);
+        }
+        chunks
+    }
+
+    fn texts(chunks: &[Chunk]) -> Vec<&str> {
+        chunks.iter().map(|chunk| chunk.text.as_str()).collect()
+    }
+
+    #[test]
+    fn fixed_windows_overlap_and_stay_on_char_boundaries() {
+        let strategy = ChunkStrategy::FixedSize {
+            size: 4,
+            overlap: 1,
+        };
+        assert_eq!(
+            texts(&chunk(strategy, "abcdefghij")),
+            ["abcd", "defg", "ghij"]
+        );
+
+        // `é` is two bytes, so windows of 3 bytes hold a single one of them
+        let chunks = chunk(strategy, "éééé");
+        assert!(chunks.iter().all(|chunk| chunk.text.len() <= 4));
+        assert_eq!(chunks.first().map(|chunk| chunk.start), Some(0));
+        assert_eq!(chunks.last().map(|chunk| chunk.end), Some("éééé".len()));
+    }
+
+    #[test]
+    fn groups_sentences_up_to_max_bytes() {
+        // sentences keep the whitespace before them, the second chunk below is 22 bytes
+        let text = "One two. Three four! Five six? Seven.";
+        let strategy = ChunkStrategy::Sentence {
+            max_bytes: 22,
+            overlap: 0,
+        };
+        assert_eq!(
+            texts(&chunk(strategy, text)),
+            ["One two. Three four!", "Five six? Seven."]
+        );
+
+        let strategy = ChunkStrategy::Sentence {
+            max_bytes: 22,
+            overlap: 1,
+        };
+        assert_eq!(
+            texts(&chunk(strategy, text)),
+            [
+                "One two. Three four!",
+                "Three four! Five six?",
+                "Five six? Seven."
+            ]
+        );
+    }
+
+    #[test]
+    fn sizes_are_bytes_not_chars() {
+        // 12 chars but 24 bytes
+        let text = "éééééé. ééééé.";
+        let strategy = ChunkStrategy::Sentence {
+            max_bytes: 16,
+            overlap: 0,
+        };
+        assert_eq!(texts(&chunk(strategy, text)), ["éééééé.", "ééééé."]);
+    }
+
+    #[test]
+    fn cuts_sentences_longer_than_max_bytes() {
+        let strategy = ChunkStrategy::Sentence {
+            max_bytes: 5,
+            overlap: 0,
+        };
+        assert_eq!(texts(&chunk(strategy, "abcdefghij")), ["abcde", "fghij"]);
+    }
+
+    #[test]
+    fn splits_markdown_on_headings_outside_code_fences() {
+        let text = "Intro.\n\n# Money\n\nFlurbos.\n\n## Rates\n\n```\n# not a heading\n```\n\n# Tools\n\nGlarbs.\n";
+        let chunks = chunk(ChunkStrategy::default(), text);
+
+        let paths: Vec<Vec<&str>> = chunks
+            .iter()
+            .map(|chunk| chunk.heading_path.iter().map(String::as_str).collect())
+            .collect();
+        assert_eq!(
+            paths,
+            [vec![], vec!["Money"], vec!["Money", "Rates"], vec!["Tools"]]
+        );
+        assert!(chunks[2].text.contains("# not a heading"));
+        assert_eq!(chunks[3].id, "doc.md#3");
+    }
+
+    #[test]
+    fn finds_sentence_boundaries() {
+        let text = "Hi there. 3.5 flurbos!\n\nNew paragraph";
+        let sentences: Vec<&str> = sentence_spans(text)
+            .into_iter()
+            .map(|(start, end)| text[start..end].trim())
+            .collect();
+        assert_eq!(sentences, ["Hi there.", "3.5 flurbos!", "New paragraph"]);
+    }
+
+    #[test]
+    fn picks_the_strategy_from_the_extension() {
+        let strategy = |path| Chunker::for_path(path, 500, 2).strategy;
+
+        let markdown = ChunkStrategy::Markdown {
+            max_bytes: 500,
+            overlap: 2,
+        };
+        assert_eq!(strategy("docs/manual.md"), markdown);
+        assert_eq!(strategy("README.MARKDOWN"), markdown);
+        let sentence = ChunkStrategy::Sentence {
+            max_bytes: 500,
+            overlap: 2,
+        };
+        assert_eq!(strategy("notes.txt"), sentence);
+        assert_eq!(strategy("NOTES"), sentence);
+        assert_eq!(
+            strategy("prices.csv"),
+            ChunkStrategy::FixedSize {
+                size: 500,
+                overlap: 50
+            }
+        );
+    }
+}
@@ -3,6 +3,9 @@ use rig::{
     Embed, OneOrMany,
     embeddings::{Embedding, EmbeddingModel, EmbeddingsBuilder},
 };
+use tracing::{Instrument, info_span};
+
+use super::usage::{Usage, UsageTracker, estimate_tokens};
 
 /// because gemini only supports embedding one document at a time,
 /// we have to use this util function with it, or else things start to break D:
@@ -10,6 +13,33 @@ pub async fn embed<T, M>(
     model: M,
     documents: Vec<T>,
 ) -> anyhow::Result<Vec<(T, OneOrMany<Embedding>)>>
+where
+    T: Embed + Send + Sync,
+    M: EmbeddingModel,
+{
+    embed_inner(model, documents, None).await
+}
+
+/// same as `embed`, but records the (estimated, gemini doesn't report them) embedding
+/// tokens under `model_name` in the tracker and respects its budget
+pub async fn embed_tracked<T, M>(
+    model: M,
+    documents: Vec<T>,
+    tracker: &UsageTracker,
+    model_name: &str,
+) -> anyhow::Result<Vec<(T, OneOrMany<Embedding>)>>
+where
+    T: Embed + Send + Sync,
+    M: EmbeddingModel,
+{
+    embed_inner(model, documents, Some((tracker, model_name))).await
+}
+
+async fn embed_inner<T, M>(
+    model: M,
+    documents: Vec<T>,
+    tracker: Option<(&UsageTracker, &str)>,
+) -> anyhow::Result<Vec<(T, OneOrMany<Embedding>)>>
 where
     T: Embed + Send + Sync,
     M: EmbeddingModel,
@@ -18,14 +48,33 @@ where
     let documents_len = documents.len();
 
     for document in documents {
+        if let Some((tracker, _)) = tracker {
+            tracker.check_budget()?;
+        }
+
         let embedding = EmbeddingsBuilder::new(model.clone())
             .document(document)?
             .build()
+            .instrument(info_span!("embed", documents = 1))
             .await?
             .into_iter()
             .next();
 
         if let Some(embedding) = embedding {
+            if let Some((tracker, model_name)) = tracker {
+                let embedding_tokens = embedding
+                    .1
+                    .iter()
+                    .map(|embedding| estimate_tokens(&embedding.document))
+                    .sum();
+                tracker.record(
+                    model_name,
+                    Usage {
+                        embedding_tokens,
+                        ..Default::default()
+                    },
+                );
+            }
             embeddings.push(embedding);
         }
     }
@@ -0,0 +1,150 @@
+use std::collections::BTreeMap;
+
+use rig::embeddings::{Embedding, EmbeddingError, EmbeddingModel};
+
+/// the name embeddings from `HashingEmbeddingModel` are tracked under
+pub const HASHING_MODEL_NAME: &str = "local-hashing";
+
+/// an `EmbeddingModel` that runs entirely in process, for air-gapped machines and CI.
+/// every word, pair of adjacent words and character trigram of a text is hashed into one
+/// of `ndims` buckets (with a hashed sign, so collisions tend to cancel out), weighted by
+/// `1 + ln(count)` and the vector is normalized.
+///
+/// it only matches on shared vocabulary and spelling, not meaning, but needs no model,
+/// no network and gives the same vector for the same text on every machine. there are no
+/// IDF weights on purpose: they would depend on the corpus, and every document already
+/// embedded would have to be embedded again whenever something is ingested
+#[derive(Clone, Debug)]
+pub struct HashingEmbeddingModel {
+    ndims: usize,
+}
+
+impl HashingEmbeddingModel {
+    pub const DEFAULT_NDIMS: usize = 768;
+
+    pub fn new(ndims: usize) -> Self {
+        assert!(
+            ndims > 0,
+            "a hashing embedding needs at least one dimension"
+        );
+        Self { ndims }
+    }
+
+    pub fn embed(&self, text: &str) -> Vec<f64> {
+        let mut counts: BTreeMap<String, (u32, f64)> = BTreeMap::new();
+        let mut add = |feature: String, weight: f64| {
+            counts.entry(feature).or_insert((0, weight)).0 += 1;
+        };
+
+        let words: Vec<String> = text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(str::to_lowercase)
+            .collect();
+        for word in &words {
+            add(format!("w:{word}"), 1.0);
+            // trigrams catch plurals, typos and compounds ("flurbos", "glarb-glarb")
+            let chars: Vec<char> = format!("<{word}>").chars().collect();
+            for trigram in chars.windows(3) {
+                add(format!("c:{}", trigram.iter().collect::<String>()), 0.5);
+            }
+        }
+        for pair in words.windows(2) {
+            add(format!("b:{} {}", pair[0], pair[1]), 0.5);
+        }
+
+        let mut vector = vec![0.0; self.ndims];
+        for (feature, (count, weight)) in counts {
+            let hash = fnv1a(feature.as_bytes());
+            let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+            vector[(hash % self.ndims as u64) as usize] +=
+                sign * weight * (1.0 + f64::from(count).ln());
+        }
+
+        let norm = vector.iter().map(|x| x * x).sum::<f64>().sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|x| *x /= norm);
+        }
+        vector
+    }
+}
+
+impl Default for HashingEmbeddingModel {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_NDIMS)
+    }
+}
+
+impl EmbeddingModel for HashingEmbeddingModel {
+    const MAX_DOCUMENTS: usize = 1024;
+
+    fn ndims(&self) -> usize {
+        self.ndims
+    }
+
+    async fn embed_texts(
+        &self,
+        texts: impl IntoIterator<Item = String> + Send,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        Ok(texts
+            .into_iter()
+            .map(|document| Embedding {
+                vec: self.embed(&document),
+                document,
+            })
+            .collect())
+    }
+}
+
+/// FNV-1a, unlike `DefaultHasher` it is guaranteed to stay the same across Rust versions,
+/// which saved stores rely on
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cosine(a: &[f64], b: &[f64]) -> f64 {
+        a.iter().zip(b).map(|(x, y)| x * y).sum()
+    }
+
+    #[test]
+    fn gives_normalized_vectors_of_ndims() {
+        let model = HashingEmbeddingModel::new(64);
+        let vector = model.embed("A flurbo is worth 10 USD");
+
+        assert_eq!(vector.len(), 64);
+        let norm = vector.iter().map(|x| x * x).sum::<f64>().sqrt();
+        assert!((norm - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn is_deterministic_and_ignores_case_and_punctuation() {
+        let model = HashingEmbeddingModel::default();
+        assert_eq!(model.embed("glarb-glarb"), model.embed("glarb-glarb"));
+        assert_eq!(model.embed("Glarb, glarb!"), model.embed("glarb glarb"));
+    }
+
+    #[test]
+    fn shared_words_are_closer_than_unrelated_ones() {
+        let model = HashingEmbeddingModel::default();
+        let query = model.embed("how much is a flurbo worth");
+
+        let related = cosine(&query, &model.embed("each flurbo is worth 10 USD"));
+        let plural = cosine(&query, &model.embed("twelve flurbos"));
+        let unrelated = cosine(&query, &model.embed("ancient tools to farm the land"));
+        assert!(related > plural, "{related} <= {plural}");
+        assert!(plural > unrelated, "{plural} <= {unrelated}");
+    }
+
+    #[test]
+    fn text_without_words_is_the_zero_vector() {
+        let model = HashingEmbeddingModel::new(8);
+        assert_eq!(model.embed(""), vec![0.0; 8]);
+        assert_eq!(model.embed(" -- !"), vec![0.0; 8]);
+    }
+}
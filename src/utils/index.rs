@@ -1,24 +1,383 @@
+use std::{
+    path::Path,
+    sync::{
+        Arc, RwLock,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use futures::future::BoxFuture;
 use rig::{
-    embeddings::EmbeddingModel,
-    vector_store::in_memory_store::{InMemoryVectorIndex, InMemoryVectorStore},
+    Embed, OneOrMany,
+    embeddings::{Embedding, EmbeddingModel},
+    tool::ToolSchema,
+    vector_store::{
+        VectorStoreError, VectorStoreIndex, VectorStoreIndexDyn,
+        in_memory_store::InMemoryVectorStore,
+    },
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
 
+use super::{
+    embed::{embed, embed_tracked},
+    usage::{Usage, UsageTracker, estimate_tokens},
+};
+
+/// an in-memory store that can still be written to after its indexes were handed out,
+/// clones and indexes all share the same documents
 #[derive(Clone)]
 pub struct VectorStore<D: Serialize + Clone, M: EmbeddingModel> {
-    vector_store: InMemoryVectorStore<D>,
+    vector_store: Arc<RwLock<InMemoryVectorStore<D>>>,
     model: M,
+    /// saved along with the documents, and what their usage is recorded under
+    model_name: String,
+    usage: Option<UsageTracker>,
+    /// whether documents were written since the store was created, loaded or saved
+    modified: Arc<AtomicBool>,
 }
 
-impl<D: Serialize + Clone, M: EmbeddingModel> VectorStore<D, M> {
-    pub fn new(vector_store: InMemoryVectorStore<D>, model: M) -> Self {
+// rig's in-memory store only takes `Eq` documents
+impl<D: Serialize + Clone + Eq, M: EmbeddingModel> VectorStore<D, M> {
+    /// `model_name` is the name of `model`, which embedded every document in `vector_store`
+    pub fn new(vector_store: InMemoryVectorStore<D>, model: M, model_name: &str) -> Self {
         Self {
-            vector_store,
+            vector_store: Arc::new(RwLock::new(vector_store)),
+            model,
+            model_name: model_name.to_string(),
+            usage: None,
+            modified: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// records every document embedded through `embed` and every query of its indexes
+    /// under the model name, and checks the tracker's budget before each of them
+    pub fn with_usage_tracker(mut self, tracker: UsageTracker) -> Self {
+        self.usage = Some(tracker);
+        self
+    }
+
+    /// queries see every document upserted after the index was created
+    pub fn index(self) -> SharedIndex<M, D> {
+        SharedIndex {
+            vector_store: self.vector_store,
+            model: self.model,
+            usage: self.usage.map(|tracker| (tracker, self.model_name)),
+        }
+    }
+
+    /// `documents` along with what embedded them, to be written to a file
+    pub(super) fn saved<T>(&self, documents: Vec<T>) -> Saved<T> {
+        Saved {
+            model: self.model_name.clone(),
+            ndims: self.model.ndims(),
+            documents,
+        }
+    }
+
+    /// embeds documents with the store's model, tracked if the store is
+    pub async fn embed<T: Embed + Send + Sync>(
+        &self,
+        documents: Vec<T>,
+    ) -> anyhow::Result<Vec<(T, OneOrMany<Embedding>)>> {
+        match &self.usage {
+            Some(tracker) => {
+                embed_tracked(self.model.clone(), documents, tracker, &self.model_name).await
+            }
+            None => embed(self.model.clone(), documents).await,
+        }
+    }
+
+    /// adds the document under `id`, replacing the one already there
+    pub fn upsert(&self, id: impl ToString, document: D, embedding: OneOrMany<Embedding>) {
+        self.vector_store
+            .write()
+            .expect("vector store poisoned")
+            .add_documents_with_ids([(id, document, embedding)]);
+        self.modified.store(true, Ordering::Relaxed);
+    }
+
+    /// whether `upsert` or `retain` changed anything since the store was created, loaded
+    /// or last saved, e.g. by the `remember` tool
+    pub fn is_modified(&self) -> bool {
+        self.modified.load(Ordering::Relaxed)
+    }
+
+    /// the first document matching `predicate`, along with its id
+    pub fn find(&self, predicate: impl Fn(&D) -> bool) -> Option<(String, D)> {
+        self.vector_store
+            .read()
+            .expect("vector store poisoned")
+            .iter()
+            .find(|(_, (document, _))| predicate(document))
+            .map(|(id, (document, _))| (id.clone(), document.clone()))
+    }
+
+    /// every document along with its id and embeddings, e.g. to persist them
+    pub fn entries(&self) -> Vec<(String, D, OneOrMany<Embedding>)> {
+        self.vector_store
+            .read()
+            .expect("vector store poisoned")
+            .iter()
+            .map(|(id, (document, embedding))| (id.clone(), document.clone(), embedding.clone()))
+            .collect()
+    }
+
+    /// drops every document `keep` returns `false` for, returning how many were dropped.
+    /// the in-memory store can't remove documents, so it is rebuilt from the kept ones
+    pub fn retain(&self, keep: impl Fn(&str, &D) -> bool) -> usize {
+        let mut vector_store = self.vector_store.write().expect("vector store poisoned");
+        let total = vector_store.iter().count();
+        let kept: Vec<_> = vector_store
+            .iter()
+            .filter(|(id, (document, _))| keep(id, document))
+            .map(|(id, (document, embedding))| (id.clone(), document.clone(), embedding.clone()))
+            .collect();
+        let removed = total - kept.len();
+
+        if removed > 0 {
+            *vector_store = InMemoryVectorStore::from_documents_with_ids(kept);
+            self.modified.store(true, Ordering::Relaxed);
+        }
+        removed
+    }
+}
+
+impl<D, M> VectorStore<D, M>
+where
+    D: Serialize + DeserializeOwned + Clone + Eq,
+    M: EmbeddingModel,
+{
+    /// reads a store written by `save`, failing unless it was embedded by `model_name`
+    /// with as many dimensions as `model`
+    pub fn load(path: impl AsRef<Path>, model: M, model_name: &str) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path)?;
+        let documents = Saved::<StoredDocument<D>>::read(path, &json, model_name, model.ndims())?
+            .into_iter()
+            .map(|stored| (stored.id, stored.document, stored.embedding));
+        Ok(Self::new(
+            InMemoryVectorStore::from_documents_with_ids(documents),
             model,
+            model_name,
+        ))
+    }
+
+    /// writes every document along with its embeddings, so they aren't embedded again
+    /// on the next run
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let stored = self
+            .entries()
+            .into_iter()
+            .map(|(id, document, embedding)| StoredDocument {
+                id,
+                document,
+                embedding,
+            })
+            .collect();
+        if let Some(dir) = path.as_ref().parent() {
+            std::fs::create_dir_all(dir)?;
         }
+        std::fs::write(path, serde_json::to_string(&self.saved(stored))?)?;
+        self.modified.store(false, Ordering::Relaxed);
+        Ok(())
     }
+}
+
+/// what `VectorStore::save` and the memory write. embeddings from another model would
+/// be compared against queries without any error, just wrong results, and two models can
+/// have the same number of dimensions, so both are kept and checked
+#[derive(Deserialize, Serialize)]
+pub(super) struct Saved<T> {
+    model: String,
+    ndims: usize,
+    documents: Vec<T>,
+}
+
+impl<T: DeserializeOwned> Saved<T> {
+    /// the documents in `json`, read from `path`, if they were embedded by `model_name`
+    /// with `ndims` dimensions
+    pub(super) fn read(
+        path: &Path,
+        json: &str,
+        model_name: &str,
+        ndims: usize,
+    ) -> anyhow::Result<Vec<T>> {
+        // files saved before the model was recorded are a bare array of documents
+        let saved: Self = serde_json::from_str(json).map_err(|err| {
+            anyhow::anyhow!(
+                "{} doesn't say which embedding model it was embedded by ({err}), embed it \
+                 again or delete it",
+                path.display()
+            )
+        })?;
+        if saved.model != model_name || saved.ndims != ndims {
+            anyhow::bail!(
+                "{} was embedded by {} ({} dimensions) but the embedding model is {model_name} \
+                 ({ndims} dimensions), embed it again with the same model or delete it",
+                path.display(),
+                saved.model,
+                saved.ndims
+            );
+        }
+        Ok(saved.documents)
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct StoredDocument<D> {
+    id: String,
+    document: D,
+    embedding: OneOrMany<Embedding>,
+}
+
+impl<M: EmbeddingModel> VectorStore<ToolSchema, M> {
+    /// a store of the embedded `ToolSet::schemas()`, keyed by tool name since that is
+    /// what `AgentBuilder::dynamic_tools` looks the retrieved ids up by
+    pub fn from_tools(
+        embeddings: Vec<(ToolSchema, OneOrMany<Embedding>)>,
+        model: M,
+        model_name: &str,
+    ) -> Self {
+        Self::new(
+            InMemoryVectorStore::from_documents_with_id_f(embeddings, |tool| tool.name.clone()),
+            model,
+            model_name,
+        )
+    }
+}
+
+pub struct SharedIndex<M: EmbeddingModel, D: Serialize + Clone> {
+    vector_store: Arc<RwLock<InMemoryVectorStore<D>>>,
+    model: M,
+    usage: Option<(UsageTracker, String)>,
+}
+
+impl<M: EmbeddingModel, D: Serialize + Clone> SharedIndex<M, D> {
+    /// like `embed_tracked`, queries are checked against the budget and their (estimated)
+    /// tokens recorded
+    async fn embed_query(&self, query: &str) -> Result<Embedding, VectorStoreError> {
+        if let Some((tracker, _)) = &self.usage {
+            tracker
+                .check_budget()
+                .map_err(|err| VectorStoreError::DatastoreError(Box::new(err)))?;
+        }
+        let embedding = self.model.embed_text(query).await?;
+        if let Some((tracker, model_name)) = &self.usage {
+            tracker.record(
+                model_name,
+                Usage {
+                    embedding_tokens: estimate_tokens(query),
+                    ..Default::default()
+                },
+            );
+        }
+        Ok(embedding)
+    }
+
+    /// the `n` documents closest to `query`, mapped through `output`. the query is embedded
+    /// before the read lock is taken, and only the results are cloned out of the store
+    async fn rank<T>(
+        &self,
+        query: &str,
+        n: usize,
+        output: impl Fn(&D) -> Result<T, VectorStoreError>,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        let query = self.embed_query(query).await?;
+
+        let store = self.vector_store.read().expect("vector store poisoned");
+        let mut scored: Vec<(f64, &String, &D)> = store
+            .iter()
+            .map(|(id, (document, embeddings))| (similarity(&query, embeddings), id, document))
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        scored
+            .into_iter()
+            .take(n)
+            .map(|(score, id, document)| Ok((score, id.clone(), output(document)?)))
+            .collect()
+    }
+}
+
+impl<M, D> VectorStoreIndex for SharedIndex<M, D>
+where
+    M: EmbeddingModel,
+    D: Serialize + Clone + Send + Sync,
+{
+    async fn top_n<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.rank(query, n, |document| {
+            Ok(serde_json::from_value(serde_json::to_value(document)?)?)
+        })
+        .await
+    }
+
+    async fn top_n_ids(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        let results = self.rank(query, n, |_| Ok(())).await?;
+        Ok(results
+            .into_iter()
+            .map(|(score, id, _)| (score, id))
+            .collect())
+    }
+}
+
+/// an index that can also score a query against chosen documents, whatever they rank
+/// among the rest of the store, e.g. to find which of the documents handed to the model
+/// back up a sentence of its answer
+pub trait ScoreIds: VectorStoreIndexDyn {
+    /// the similarity of `query` to each of `ids`, highest first. unknown ids are left out
+    fn score_ids<'a>(
+        &'a self,
+        query: &'a str,
+        ids: &'a [String],
+    ) -> BoxFuture<'a, Result<Vec<(f64, String)>, VectorStoreError>>;
+}
+
+impl<M, D> ScoreIds for SharedIndex<M, D>
+where
+    M: EmbeddingModel,
+    D: Serialize + Clone + Send + Sync,
+{
+    fn score_ids<'a>(
+        &'a self,
+        query: &'a str,
+        ids: &'a [String],
+    ) -> BoxFuture<'a, Result<Vec<(f64, String)>, VectorStoreError>> {
+        Box::pin(async move {
+            let query = self.embed_query(query).await?;
+
+            let store = self.vector_store.read().expect("vector store poisoned");
+            let mut scored: Vec<(f64, String)> = store
+                .iter()
+                .filter(|(id, _)| ids.contains(id))
+                .map(|(id, (_, embeddings))| (similarity(&query, embeddings), id.clone()))
+                .collect();
+            scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+            Ok(scored)
+        })
+    }
+}
+
+/// cosine similarity to the closest of a document's embeddings, like rig's own stores
+fn similarity(query: &Embedding, embeddings: &OneOrMany<Embedding>) -> f64 {
+    embeddings
+        .iter()
+        .map(|embedding| cosine(&query.vec, &embedding.vec))
+        .fold(f64::NEG_INFINITY, f64::max)
+}
 
-    pub fn index(self) -> InMemoryVectorIndex<M, D> {
-        self.vector_store.index(self.model)
+/// 0 for empty vectors, instead of NaN
+fn cosine(a: &[f64], b: &[f64]) -> f64 {
+    let norm = |v: &[f64]| v.iter().map(|x| x * x).sum::<f64>().sqrt();
+    let norms = norm(a) * norm(b);
+    if norms == 0.0 {
+        return 0.0;
     }
+    a.iter().zip(b).map(|(x, y)| x * y).sum::<f64>() / norms
 }
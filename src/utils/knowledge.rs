@@ -0,0 +1,287 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rig::{
+    Embed, OneOrMany,
+    embeddings::{EmbedError, Embedding, EmbeddingModel, TextEmbedder},
+};
+use serde::{Deserialize, Serialize};
+
+use super::{chunk::Chunk, index::VectorStore, session::SessionHandle};
+
+#[derive(Embed, Deserialize, Serialize, Clone, Debug, Eq, PartialEq, Default)]
+pub struct WordDefinition {
+    pub id: String,
+    pub word: String,
+    #[embed]
+    pub definitions: Vec<String>,
+    /// `None` for the entries the knowledge base started with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<KnowledgeSource>,
+}
+
+/// where an entry written by the agent came from
+#[derive(Deserialize, Serialize, Clone, Debug, Eq, PartialEq)]
+pub struct KnowledgeSource {
+    pub session_id: String,
+    /// unix timestamp, in seconds
+    pub created_at: u64,
+}
+
+/// everything that can live in the vector store, glossary entries and chunks of
+/// long-form documents alike
+#[derive(Deserialize, Serialize, Clone, Debug, Eq, PartialEq)]
+#[serde(untagged)]
+pub enum KnowledgeDocument {
+    Word(WordDefinition),
+    Chunk(Chunk),
+}
+
+impl Embed for KnowledgeDocument {
+    fn embed(&self, embedder: &mut TextEmbedder) -> Result<(), EmbedError> {
+        match self {
+            Self::Word(word) => word.embed(embedder),
+            Self::Chunk(chunk) => chunk.embed(embedder),
+        }
+    }
+}
+
+/// an entry waiting for review, already embedded so approving it is instant
+#[derive(Clone, Debug)]
+pub struct PendingEntry {
+    pub entry: WordDefinition,
+    embedding: OneOrMany<Embedding>,
+}
+
+/// the writable side of the knowledge base: new or amended glossary entries are embedded
+/// and upserted into the same store `lookup` and the dynamic context read from
+#[derive(Clone)]
+pub struct KnowledgeBase<M: EmbeddingModel> {
+    store: VectorStore<KnowledgeDocument, M>,
+    session: SessionHandle,
+    /// `None` if entries become searchable right away
+    review: Option<Arc<Mutex<Vec<PendingEntry>>>>,
+}
+
+impl<M: EmbeddingModel> KnowledgeBase<M> {
+    pub fn new(store: VectorStore<KnowledgeDocument, M>, session: SessionHandle) -> Self {
+        Self {
+            store,
+            session,
+            review: None,
+        }
+    }
+
+    /// holds every write in a queue until it is `approve`d
+    pub fn with_review(mut self) -> Self {
+        self.review = Some(Arc::default());
+        self
+    }
+
+    /// adds `definition` to the entry for `word` (or replaces its definitions), creating
+    /// it if needed. returns the entry, and whether it is searchable yet
+    pub async fn remember(
+        &self,
+        word: &str,
+        definition: &str,
+        replace: bool,
+    ) -> anyhow::Result<(WordDefinition, bool)> {
+        let mut entry = self.current(word).unwrap_or_else(|| WordDefinition {
+            id: entry_id(word),
+            word: word.to_string(),
+            ..Default::default()
+        });
+        if replace {
+            entry.definitions.clear();
+        }
+        entry.definitions.push(definition.to_string());
+        entry.source = Some(KnowledgeSource {
+            session_id: self.session.id(),
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        });
+
+        let (document, embedding) = self
+            .store
+            .embed(vec![KnowledgeDocument::Word(entry.clone())])
+            .await?
+            .remove(0);
+
+        match &self.review {
+            Some(review) => {
+                let mut review = review.lock().expect("review queue poisoned");
+                review.retain(|pending| pending.entry.id != entry.id);
+                review.push(PendingEntry {
+                    entry: entry.clone(),
+                    embedding,
+                });
+                Ok((entry, false))
+            }
+            None => {
+                self.store.upsert(&entry.id, document, embedding);
+                Ok((entry, true))
+            }
+        }
+    }
+
+    /// entries waiting for review, oldest first
+    pub fn pending(&self) -> Vec<WordDefinition> {
+        self.review
+            .as_ref()
+            .map(|review| {
+                let review = review.lock().expect("review queue poisoned");
+                review.iter().map(|pending| pending.entry.clone()).collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// makes the pending entry `id` searchable, `false` if there is no such entry
+    pub fn approve(&self, id: &str) -> bool {
+        let Some(pending) = self.take_pending(id) else {
+            return false;
+        };
+        self.store.upsert(
+            pending.entry.id.clone(),
+            KnowledgeDocument::Word(pending.entry),
+            pending.embedding,
+        );
+        true
+    }
+
+    /// drops the pending entry `id`, `false` if there is no such entry
+    pub fn reject(&self, id: &str) -> bool {
+        self.take_pending(id).is_some()
+    }
+
+    fn take_pending(&self, id: &str) -> Option<PendingEntry> {
+        let mut review = self.review.as_ref()?.lock().expect("review queue poisoned");
+        let position = review.iter().position(|pending| pending.entry.id == id)?;
+        Some(review.remove(position))
+    }
+
+    /// the latest version of the entry for `word`, pending ones included so amendments
+    /// build on each other
+    fn current(&self, word: &str) -> Option<WordDefinition> {
+        let pending = self.review.as_ref().and_then(|review| {
+            let review = review.lock().expect("review queue poisoned");
+            review
+                .iter()
+                .find(|pending| pending.entry.word.eq_ignore_ascii_case(word))
+                .map(|pending| pending.entry.clone())
+        });
+
+        pending.or_else(|| {
+            let (id, document) = self.store.find(|document| match document {
+                KnowledgeDocument::Word(entry) => entry.word.eq_ignore_ascii_case(word),
+                KnowledgeDocument::Chunk(_) => false,
+            })?;
+            let KnowledgeDocument::Word(mut entry) = document else {
+                return None;
+            };
+            // the store id is what gets replaced on upsert
+            entry.id = id;
+            Some(entry)
+        })
+    }
+}
+
+fn entry_id(word: &str) -> String {
+    let slug: String = word
+        .trim()
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect();
+    format!("word-{slug}")
+}
+
+#[cfg(test)]
+mod tests {
+    use rig::vector_store::in_memory_store::InMemoryVectorStore;
+
+    use super::*;
+    use crate::utils::{HASHING_MODEL_NAME, HashingEmbeddingModel};
+
+    fn knowledge_base() -> KnowledgeBase<HashingEmbeddingModel> {
+        let store = VectorStore::new(
+            InMemoryVectorStore::from_documents_with_ids(Vec::<(
+                String,
+                KnowledgeDocument,
+                OneOrMany<Embedding>,
+            )>::new()),
+            HashingEmbeddingModel::default(),
+            HASHING_MODEL_NAME,
+        );
+        KnowledgeBase::new(store, SessionHandle::new())
+    }
+
+    fn searchable(knowledge: &KnowledgeBase<HashingEmbeddingModel>, word: &str) -> bool {
+        knowledge
+            .store
+            .find(
+                |document| matches!(document, KnowledgeDocument::Word(entry) if entry.word == word),
+            )
+            .is_some()
+    }
+
+    #[tokio::test]
+    async fn approved_entries_become_searchable() {
+        let knowledge = knowledge_base().with_review();
+        let (entry, found) = knowledge
+            .remember("glarb", "A blue fruit.", false)
+            .await
+            .unwrap();
+        assert!(!found);
+        assert!(!searchable(&knowledge, "glarb"));
+
+        // amending a pending entry amends it in the queue
+        knowledge
+            .remember("Glarb", "Also a currency.", false)
+            .await
+            .unwrap();
+        let pending = knowledge.pending();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(
+            pending[0].definitions,
+            ["A blue fruit.", "Also a currency."]
+        );
+
+        assert!(knowledge.approve(&entry.id));
+        assert!(knowledge.pending().is_empty());
+        assert!(searchable(&knowledge, "glarb"));
+        assert!(!knowledge.approve(&entry.id));
+    }
+
+    #[tokio::test]
+    async fn rejected_entries_are_dropped() {
+        let knowledge = knowledge_base().with_review();
+        let (entry, _) = knowledge
+            .remember("flarp", "A loud noise.", false)
+            .await
+            .unwrap();
+
+        assert!(!knowledge.approve("word-unknown"));
+        assert!(knowledge.reject(&entry.id));
+        assert!(knowledge.pending().is_empty());
+        assert!(!searchable(&knowledge, "flarp"));
+        assert!(!knowledge.reject(&entry.id));
+    }
+
+    #[tokio::test]
+    async fn without_review_entries_are_searchable_right_away() {
+        let knowledge = knowledge_base();
+        let (entry, found) = knowledge
+            .remember("glarb", "A blue fruit.", false)
+            .await
+            .unwrap();
+
+        assert!(found);
+        assert!(searchable(&knowledge, "glarb"));
+        assert!(knowledge.pending().is_empty());
+        assert!(!knowledge.reject(&entry.id));
+    }
+}
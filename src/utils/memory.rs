@@ -0,0 +1,275 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rig::{
+    Embed, OneOrMany,
+    agent::Agent,
+    completion::{Completion, CompletionModel},
+    embeddings::{Embedding, EmbeddingModel},
+    message::AssistantContent,
+    vector_store::in_memory_store::InMemoryVectorStore,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    index::{Saved, SharedIndex, VectorStore},
+    outcome::TurnOutcome,
+    session::SessionHandle,
+    usage::{ReportsUsage, UsageTracker},
+};
+
+/// the preamble of the agent given to `MemoryStore::record_turn`
+pub const MEMORY_PREAMBLE: &str = "You extract long-term memories from a conversation between a user and an assistant. List the facts about the user worth remembering in future conversations (what they own, prefer, plan or told you about themselves), one short self-contained sentence per line, without numbering. Leave out the assistant's calculations and general knowledge. If there is nothing worth remembering, answer NONE.";
+
+/// a salient fact from a past turn
+#[derive(Embed, Deserialize, Serialize, Clone, Debug, Eq, PartialEq)]
+pub struct Memory {
+    pub id: String,
+    #[embed]
+    pub fact: String,
+    /// the session the fact was learned in
+    pub session_id: String,
+    /// unix timestamp, in seconds
+    pub created_at: u64,
+}
+
+#[derive(Deserialize, Serialize)]
+struct StoredMemory {
+    #[serde(flatten)]
+    memory: Memory,
+    embedding: OneOrMany<Embedding>,
+}
+
+/// the episodic memory of a single user, persisted (embeddings included) to
+/// `{dir}/{user_id}.json` so it carries over across sessions and runs. its `index` is meant
+/// for `AgentBuilder::dynamic_context`, next to the knowledge base's
+#[derive(Clone)]
+pub struct MemoryStore<M: EmbeddingModel> {
+    path: PathBuf,
+    store: VectorStore<Memory, M>,
+    session: SessionHandle,
+    /// the summarizer's completions are recorded under the model name
+    usage: Option<(UsageTracker, String)>,
+}
+
+impl<M: EmbeddingModel> MemoryStore<M> {
+    /// loads the memories of `user_id`, starting empty if there are none yet. fails on an
+    /// empty id, or memories embedded by another model than `model_name`
+    pub fn open(
+        dir: impl AsRef<Path>,
+        user_id: &str,
+        model: M,
+        model_name: &str,
+        session: SessionHandle,
+    ) -> anyhow::Result<Self> {
+        if user_id.is_empty() {
+            anyhow::bail!("The user id of a memory store must not be empty");
+        }
+        let path = dir.as_ref().join(format!("{}.json", file_name(user_id)));
+        let stored: Vec<StoredMemory> = match std::fs::read_to_string(&path) {
+            Ok(json) => Saved::read(&path, &json, model_name, model.ndims())?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
+        let documents = stored
+            .into_iter()
+            .map(|stored| (stored.memory.id.clone(), stored.memory, stored.embedding));
+
+        Ok(Self {
+            path,
+            store: VectorStore::new(
+                InMemoryVectorStore::from_documents_with_ids(documents),
+                model,
+                model_name,
+            ),
+            session,
+            usage: None,
+        })
+    }
+
+    /// records the embeddings of memories and queries under the embedding model's name,
+    /// and the summarizer's completions under `completion_model`, within the tracker's
+    /// budget
+    pub fn with_usage_tracker(mut self, tracker: UsageTracker, completion_model: &str) -> Self {
+        self.store = self.store.with_usage_tracker(tracker.clone());
+        self.usage = Some((tracker, completion_model.to_string()));
+        self
+    }
+
+    pub fn index(&self) -> SharedIndex<M, Memory> {
+        self.store.clone().index()
+    }
+
+    /// every memory, oldest first
+    pub fn memories(&self) -> Vec<Memory> {
+        let mut memories: Vec<Memory> = self
+            .store
+            .entries()
+            .into_iter()
+            .map(|(_, memory, _)| memory)
+            .collect();
+        memories.sort_by(|a, b| (a.created_at, &a.id).cmp(&(b.created_at, &b.id)));
+        memories
+    }
+
+    /// has `summarizer` (built with `MEMORY_PREAMBLE`) pick the facts worth keeping out
+    /// of a finished turn, and stores the new ones. returns what was stored
+    pub async fn record_turn<C>(
+        &self,
+        summarizer: &Agent<C>,
+        prompt: &str,
+        outcome: &TurnOutcome,
+    ) -> anyhow::Result<Vec<Memory>>
+    where
+        C: CompletionModel,
+        C::Response: ReportsUsage,
+    {
+        // a paused turn has no answer to learn from yet
+        if outcome.pending_approval.is_some() {
+            return Ok(Vec::new());
+        }
+
+        if let Some((tracker, _)) = &self.usage {
+            tracker.check_budget()?;
+        }
+        let exchange = format!("User: {prompt}\nAssistant: {}", outcome.text);
+        let response = summarizer
+            .completion(exchange.as_str(), Vec::new())
+            .await?
+            .send()
+            .await?;
+        if let (Some((tracker, model_name)), Some(usage)) =
+            (&self.usage, response.raw_response.usage())
+        {
+            tracker.record(model_name, usage);
+        }
+        let summary: String = response
+            .choice
+            .iter()
+            .filter_map(|content| match content {
+                AssistantContent::Text(text) => Some(text.text.as_str()),
+                AssistantContent::ToolCall(_) => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let known: Vec<String> = self
+            .memories()
+            .into_iter()
+            .map(|memory| memory.fact.to_lowercase())
+            .collect();
+        let session_id = self.session.id();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let created_at = now.as_secs();
+        // ids at second resolution would let the next turn of the same second replace
+        // this one's memories
+        let stamp = now.as_nanos();
+
+        let mut memories = Vec::new();
+        for line in summary.lines() {
+            let fact = line.trim().trim_start_matches(['-', '*']).trim();
+            if fact.is_empty()
+                || fact.eq_ignore_ascii_case("none")
+                || known.contains(&fact.to_lowercase())
+            {
+                continue;
+            }
+            memories.push(Memory {
+                id: format!("memory-{session_id}-{stamp}-{}", memories.len()),
+                fact: fact.to_string(),
+                session_id: session_id.clone(),
+                created_at,
+            });
+        }
+        if memories.is_empty() {
+            return Ok(memories);
+        }
+
+        tracing::info!(count = memories.len(), "storing memories");
+        for (memory, embedding) in self.store.embed(memories.clone()).await? {
+            self.store.upsert(memory.id.clone(), memory, embedding);
+        }
+        self.save()?;
+        Ok(memories)
+    }
+
+    /// `false` if there is no memory `id`
+    pub fn forget(&self, id: &str) -> anyhow::Result<bool> {
+        let removed = self.store.retain(|memory_id, _| memory_id != id);
+        if removed > 0 {
+            self.save()?;
+        }
+        Ok(removed > 0)
+    }
+
+    /// forgets everything about the user, returning how many memories were dropped
+    pub fn forget_all(&self) -> anyhow::Result<usize> {
+        let removed = self.store.retain(|_, _| false);
+        self.save()?;
+        Ok(removed)
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        let stored = self
+            .store
+            .entries()
+            .into_iter()
+            .map(|(_, memory, embedding)| StoredMemory { memory, embedding })
+            .collect();
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(
+            &self.path,
+            serde_json::to_string_pretty(&self.store.saved(stored))?,
+        )?;
+        Ok(())
+    }
+}
+
+/// keeps user ids from escaping the memory directory. anything but ASCII letters, digits,
+/// `-` and `_` is percent-encoded, so two ids never share a file
+fn file_name(user_id: &str) -> String {
+    user_id
+        .bytes()
+        .map(|byte| {
+            if byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_' {
+                char::from(byte).to_string()
+            } else {
+                format!("%{byte:02X}")
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::HashingEmbeddingModel;
+
+    #[test]
+    fn file_names_stay_in_the_directory() {
+        assert_eq!(file_name("alice_01-b"), "alice_01-b");
+        assert_eq!(file_name("../etc/passwd"), "%2E%2E%2Fetc%2Fpasswd");
+        assert_eq!(file_name("a b"), "a%20b");
+        assert_eq!(file_name("zoë"), "zo%C3%AB");
+        // the escape character is escaped too, so ids can't be forged into each other
+        assert_ne!(file_name("a%20b"), file_name("a b"));
+    }
+
+    #[test]
+    fn empty_user_ids_are_rejected() {
+        let opened = MemoryStore::open(
+            std::env::temp_dir(),
+            "",
+            HashingEmbeddingModel::default(),
+            crate::utils::HASHING_MODEL_NAME,
+            SessionHandle::new(),
+        );
+        assert!(opened.is_err());
+    }
+}
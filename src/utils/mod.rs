@@ -1,7 +1,27 @@
 mod agent;
+mod approval;
+mod chunk;
 mod embed;
+mod hashing;
 mod index;
+mod knowledge;
+mod memory;
+mod outcome;
+mod provenance;
+mod session;
+mod telemetry;
+mod usage;
+mod validate;
 
 pub use agent::MultiTurnAgent;
-pub use embed::embed;
+pub use approval::ApprovalDecision;
+pub use chunk::Chunker;
+pub use embed::embed_tracked;
+pub use hashing::{HASHING_MODEL_NAME, HashingEmbeddingModel};
 pub use index::VectorStore;
+pub use knowledge::{KnowledgeBase, KnowledgeDocument, WordDefinition};
+pub use memory::{MEMORY_PREAMBLE, MemoryStore};
+pub use outcome::{TurnEvent, TurnOutcome};
+pub use session::{SessionHandle, SessionSnapshot};
+pub use telemetry::{LogFormat, init_tracing};
+pub use usage::{Budget, ModelPrice, PriceTable, ReportsUsage, UsageTracker};
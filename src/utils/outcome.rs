@@ -0,0 +1,61 @@
+use std::time::Duration;
+
+use rig::message::Message;
+use serde::Serialize;
+use serde_json::Value;
+
+use super::{
+    approval::{ApprovalDecision, PendingToolCall},
+    provenance::Provenance,
+    usage::Usage,
+};
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ToolInvocation {
+    pub name: String,
+    /// the arguments before repairs, as the model sent them or as the user edited them
+    pub arguments: Value,
+    /// the serialized tool output, `None` if the tool failed
+    pub result: Option<String>,
+    pub error: Option<String>,
+    /// coercions applied to `arguments` before the call, e.g. a numeric string turned
+    /// into a number
+    pub repairs: Vec<String>,
+    /// `None` if the call didn't need approval
+    pub approval: Option<ApprovalDecision>,
+    pub duration: Duration,
+}
+
+/// everything that happened during a single `multi_turn_prompt` (or `resume`)
+#[derive(Clone, Debug, Serialize)]
+pub struct TurnOutcome {
+    /// the final answer
+    pub text: String,
+    /// texts the model produced alongside its tool calls (chain of thought)
+    pub intermediate_texts: Vec<String>,
+    pub tool_calls: Vec<ToolInvocation>,
+    /// how many completion requests were sent
+    pub turns: usize,
+    /// `None` if the provider never reported token usage
+    pub usage: Option<Usage>,
+    /// every message appended to the chat history, in order
+    pub messages: Vec<Message>,
+    pub provenance: Provenance,
+    /// set when the turn paused on a call needing approval, `text` is empty until it is
+    /// resumed
+    pub pending_approval: Option<PendingToolCall>,
+}
+
+/// what happens during a turn, as it happens, see `MultiTurnAgent::with_event_handler`
+#[derive(Clone, Copy, Debug)]
+pub enum TurnEvent<'a> {
+    /// a text from the model, either the final answer or one produced alongside a tool
+    /// call
+    Text(&'a str),
+    /// a tool is about to be called
+    ToolCall { name: &'a str, arguments: &'a Value },
+    /// a tool call finished, failed or was denied
+    ToolResult(&'a ToolInvocation),
+    /// the turn paused until it is resumed
+    ApprovalNeeded(&'a PendingToolCall),
+}
@@ -0,0 +1,96 @@
+use rig::vector_store::VectorStoreError;
+use serde::Serialize;
+use tracing::{Instrument, field::Empty, info_span};
+
+use super::{chunk::sentence_spans, index::ScoreIds};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentOrigin {
+    /// pulled in automatically by the agent's dynamic context
+    DynamicContext,
+    /// returned by an explicit call to the `lookup` tool
+    Lookup,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct RetrievedDocument {
+    pub id: String,
+    pub score: f64,
+    pub origin: DocumentOrigin,
+}
+
+/// a sentence of the final answer and the retrieved documents that back it up
+#[derive(Clone, Debug, Serialize)]
+pub struct Citation {
+    pub sentence: String,
+    pub documents: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Provenance {
+    pub documents: Vec<RetrievedDocument>,
+    /// only filled when citations are enabled on the agent
+    pub citations: Vec<Citation>,
+}
+
+impl Provenance {
+    pub fn record_document(&mut self, id: String, score: f64, origin: DocumentOrigin) {
+        if !self.documents.iter().any(|doc| doc.id == id) {
+            self.documents.push(RetrievedDocument { id, score, origin });
+        }
+    }
+
+    /// unique ids of every document that made it into the conversation
+    pub fn document_ids(&self) -> Vec<&str> {
+        self.documents.iter().map(|doc| doc.id.as_str()).collect()
+    }
+
+    /// maps each sentence of `text` to the retrieved documents it is most similar to,
+    /// as long as their similarity is at least `threshold`. sentences are only scored
+    /// against the retrieved documents, so the rest of the store can't crowd them out
+    pub async fn cite(
+        &mut self,
+        index: &(dyn ScoreIds + Send + Sync),
+        text: &str,
+        threshold: f64,
+    ) -> Result<(), VectorStoreError> {
+        let retrieved: Vec<String> = self.documents.iter().map(|doc| doc.id.clone()).collect();
+        if retrieved.is_empty() {
+            return Ok(());
+        }
+
+        let mut citations = Vec::new();
+        for (start, end) in sentence_spans(text) {
+            let sentence = text[start..end].trim();
+
+            let span = info_span!(
+                "vector_search",
+                query = sentence,
+                n = retrieved.len(),
+                results = Empty
+            );
+            let results = index
+                .score_ids(sentence, &retrieved)
+                .instrument(span.clone())
+                .await?;
+            span.record("results", results.len());
+
+            let documents: Vec<String> = results
+                .into_iter()
+                .filter(|(score, _)| *score >= threshold)
+                .map(|(_, id)| id)
+                .collect();
+
+            if !documents.is_empty() {
+                citations.push(Citation {
+                    sentence: sentence.to_string(),
+                    documents,
+                });
+            }
+        }
+
+        self.citations = citations;
+        Ok(())
+    }
+}
@@ -0,0 +1,54 @@
+use std::{
+    path::Path,
+    sync::{Arc, RwLock},
+};
+
+use rig::message::Message;
+use serde::{Deserialize, Serialize};
+
+use crate::tools::LedgerEntry;
+
+/// everything needed to pick a `MultiTurnAgent` session back up later
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SessionSnapshot {
+    pub session_id: String,
+    /// the `MultiTurnAgent` profile the session was in, `None` in snapshots saved before
+    /// there were profiles
+    #[serde(default)]
+    pub profile: Option<String>,
+    pub chat_history: Vec<Message>,
+    /// empty if the agent has no ledger
+    #[serde(default)]
+    pub ledger: Vec<LedgerEntry>,
+}
+
+impl SessionSnapshot {
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+}
+
+/// the id of the session a `MultiTurnAgent` is currently in, for tools that record where
+/// something came from. clones share the id, hand one to
+/// `MultiTurnAgent::with_session_handle` to keep it up to date
+#[derive(Clone, Debug, Default)]
+pub struct SessionHandle(Arc<RwLock<String>>);
+
+impl SessionHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn id(&self) -> String {
+        self.0.read().expect("session handle poisoned").clone()
+    }
+
+    pub(super) fn set(&self, session_id: &str) {
+        *self.0.write().expect("session handle poisoned") = session_id.to_string();
+    }
+}
@@ -0,0 +1,265 @@
+use std::{
+    collections::hash_map::RandomState,
+    fs::File,
+    hash::{BuildHasher, Hasher},
+    io::Write,
+    path::PathBuf,
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::{Map, Value, json};
+use tracing::{
+    Event, Subscriber,
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+};
+use tracing_subscriber::{
+    EnvFilter, Layer,
+    fmt::format::FmtSpan,
+    layer::{Context, SubscriberExt},
+    registry::LookupSpan,
+    util::SubscriberInitExt,
+};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// human readable, multi-line output on stdout
+    #[default]
+    Pretty,
+    /// one JSON object per event on stdout
+    Json,
+    /// finished spans appended to a file as JSON lines, OpenTelemetry-style span records
+    /// (`traceId`, `spanId`, `parentSpanId`, timestamps in unix nanos and attributes).
+    /// they aren't wrapped in OTLP's `resourceSpans` / `scopeSpans`, so a collector can't
+    /// read the file as is
+    OtelFile(PathBuf),
+}
+
+impl LogFormat {
+    /// reads `RAG_LOG_FORMAT` (`pretty`, `json` or `otel`) and, for `otel`,
+    /// `RAG_LOG_FILE` (defaults to `spans.jsonl`)
+    pub fn from_env() -> Self {
+        match std::env::var("RAG_LOG_FORMAT").as_deref() {
+            Ok("json") => Self::Json,
+            Ok("otel") => Self::OtelFile(
+                std::env::var("RAG_LOG_FILE")
+                    .unwrap_or_else(|_| "spans.jsonl".to_string())
+                    .into(),
+            ),
+            _ => Self::Pretty,
+        }
+    }
+}
+
+/// installs the global subscriber, filtered by `RUST_LOG`, or `default_filter` when it
+/// isn't set (e.g. `rag_tool_test=info`)
+pub fn init_tracing(format: LogFormat, default_filter: &str) -> anyhow::Result<()> {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(default_filter));
+    let registry = tracing_subscriber::registry().with(filter);
+
+    match format {
+        LogFormat::Pretty => registry
+            .with(
+                tracing_subscriber::fmt::layer()
+                    .pretty()
+                    .with_span_events(FmtSpan::CLOSE),
+            )
+            .try_init()?,
+        LogFormat::Json => registry
+            .with(
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .with_current_span(true)
+                    .with_span_list(true)
+                    .with_span_events(FmtSpan::CLOSE),
+            )
+            .try_init()?,
+        LogFormat::OtelFile(path) => registry
+            .with(OtelFileLayer::new(
+                File::options().create(true).append(true).open(path)?,
+            ))
+            .try_init()?,
+    }
+
+    Ok(())
+}
+
+struct OtelFileLayer {
+    file: Mutex<File>,
+    /// mixed into the span / trace ids so they are unique across runs
+    seed: RandomState,
+    counter: AtomicU64,
+}
+
+struct SpanData {
+    trace_id: u128,
+    span_id: u64,
+    parent_span_id: Option<u64>,
+    start: SystemTime,
+    attributes: Map<String, Value>,
+    events: Vec<Value>,
+}
+
+impl OtelFileLayer {
+    fn new(file: File) -> Self {
+        Self {
+            file: Mutex::new(file),
+            seed: RandomState::new(),
+            counter: AtomicU64::new(0),
+        }
+    }
+
+    fn random_u64(&self) -> u64 {
+        let mut hasher = self.seed.build_hasher();
+        hasher.write_u64(self.counter.fetch_add(1, Ordering::Relaxed));
+        hasher.finish()
+    }
+
+    fn write(&self, line: Value) {
+        let mut file = self.file.lock().expect("span file poisoned");
+        // losing a span is better than taking the whole app down
+        let _ = writeln!(file, "{line}");
+    }
+}
+
+fn unix_nanos(time: SystemTime) -> String {
+    // OTLP/JSON encodes 64 bit integers as strings
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+        .to_string()
+}
+
+fn otel_attributes(attributes: &Map<String, Value>) -> Value {
+    attributes
+        .iter()
+        .map(|(key, value)| {
+            let value = match value {
+                Value::Bool(value) => json!({ "boolValue": value }),
+                Value::Number(value) if value.is_f64() => json!({ "doubleValue": value }),
+                Value::Number(value) => json!({ "intValue": value.to_string() }),
+                Value::String(value) => json!({ "stringValue": value }),
+                value => json!({ "stringValue": value.to_string() }),
+            };
+            json!({ "key": key, "value": value })
+        })
+        .collect()
+}
+
+impl<S> Layer<S> for OtelFileLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+
+        let parent = span.parent().and_then(|parent| {
+            parent
+                .extensions()
+                .get::<SpanData>()
+                .map(|data| (data.trace_id, data.span_id))
+        });
+        let trace_id = parent.map(|(trace_id, _)| trace_id).unwrap_or_else(|| {
+            (u128::from(self.random_u64()) << 64) | u128::from(self.random_u64())
+        });
+
+        let mut attributes = Map::new();
+        attrs.record(&mut JsonVisitor(&mut attributes));
+
+        span.extensions_mut().insert(SpanData {
+            trace_id,
+            span_id: self.random_u64(),
+            parent_span_id: parent.map(|(_, span_id)| span_id),
+            start: SystemTime::now(),
+            attributes,
+            events: Vec::new(),
+        });
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        if let Some(data) = span.extensions_mut().get_mut::<SpanData>() {
+            values.record(&mut JsonVisitor(&mut data.attributes));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.event_span(event) else {
+            return;
+        };
+
+        let mut attributes = Map::new();
+        event.record(&mut JsonVisitor(&mut attributes));
+        let name = attributes
+            .remove("message")
+            .and_then(|message| message.as_str().map(str::to_string))
+            .unwrap_or_else(|| event.metadata().name().to_string());
+
+        if let Some(data) = span.extensions_mut().get_mut::<SpanData>() {
+            data.events.push(json!({
+                "timeUnixNano": unix_nanos(SystemTime::now()),
+                "name": name,
+                "attributes": otel_attributes(&attributes),
+            }));
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(data) = span.extensions_mut().remove::<SpanData>() else {
+            return;
+        };
+
+        self.write(json!({
+            "traceId": format!("{:032x}", data.trace_id),
+            "spanId": format!("{:016x}", data.span_id),
+            "parentSpanId": data.parent_span_id.map(|id| format!("{id:016x}")).unwrap_or_default(),
+            "name": span.name(),
+            "kind": 1,
+            "startTimeUnixNano": unix_nanos(data.start),
+            "endTimeUnixNano": unix_nanos(SystemTime::now()),
+            "attributes": otel_attributes(&data.attributes),
+            "events": data.events,
+        }));
+    }
+}
+
+struct JsonVisitor<'a>(&'a mut Map<String, Value>);
+
+impl Visit for JsonVisitor<'_> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), json!(format!("{value:?}")));
+    }
+}
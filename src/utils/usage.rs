@@ -0,0 +1,274 @@
+use std::{
+    collections::HashMap,
+    ops::{Add, AddAssign},
+    sync::{Arc, Mutex},
+};
+
+use rig::providers::{gemini::completion::gemini_api_types::GenerateContentResponse, openai};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub embedding_tokens: u64,
+}
+
+impl Usage {
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens + self.embedding_tokens
+    }
+}
+
+impl Add for Usage {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self {
+            prompt_tokens: self.prompt_tokens + rhs.prompt_tokens,
+            completion_tokens: self.completion_tokens + rhs.completion_tokens,
+            embedding_tokens: self.embedding_tokens + rhs.embedding_tokens,
+        }
+    }
+}
+
+impl AddAssign for Usage {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+/// implemented by the raw completion responses of providers that report token usage
+pub trait ReportsUsage {
+    fn usage(&self) -> Option<Usage>;
+}
+
+impl ReportsUsage for GenerateContentResponse {
+    fn usage(&self) -> Option<Usage> {
+        self.usage_metadata.as_ref().map(|usage| Usage {
+            prompt_tokens: usage.prompt_token_count.max(0) as u64,
+            completion_tokens: usage.candidates_token_count.max(0) as u64,
+            embedding_tokens: 0,
+        })
+    }
+}
+
+impl ReportsUsage for openai::CompletionResponse {
+    fn usage(&self) -> Option<Usage> {
+        self.usage.as_ref().map(|usage| Usage {
+            prompt_tokens: usage.prompt_tokens as u64,
+            completion_tokens: usage.total_tokens.saturating_sub(usage.prompt_tokens) as u64,
+            embedding_tokens: 0,
+        })
+    }
+}
+
+/// rough token count for providers that don't report one (like gemini's embeddings),
+/// assumes ~4 characters per token
+pub fn estimate_tokens(text: &str) -> u64 {
+    text.chars().count().div_ceil(4) as u64
+}
+
+/// prices in USD per million tokens
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub prompt: f64,
+    pub completion: f64,
+    pub embedding: f64,
+}
+
+impl ModelPrice {
+    pub fn cost(&self, usage: &Usage) -> f64 {
+        (usage.prompt_tokens as f64 * self.prompt
+            + usage.completion_tokens as f64 * self.completion
+            + usage.embedding_tokens as f64 * self.embedding)
+            / 1_000_000.0
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PriceTable(HashMap<String, ModelPrice>);
+
+impl PriceTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_model(mut self, model: impl Into<String>, price: ModelPrice) -> Self {
+        self.0.insert(model.into(), price);
+        self
+    }
+
+    /// models missing from the table are considered free
+    pub fn cost(&self, model: &str, usage: &Usage) -> f64 {
+        self.0
+            .get(model)
+            .map(|price| price.cost(usage))
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Budget {
+    pub max_tokens: Option<u64>,
+    /// in USD
+    pub max_cost: Option<f64>,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Budget exceeded: used {tokens} tokens (${cost:.6}), budget is {budget:?}")]
+pub struct BudgetExceeded {
+    pub tokens: u64,
+    pub cost: f64,
+    pub budget: Budget,
+}
+
+#[derive(Default)]
+struct TrackerState {
+    per_model: HashMap<String, Usage>,
+    turns: Vec<Usage>,
+}
+
+/// accumulates usage for a whole session, cloning it shares the same totals so the
+/// same tracker can be handed to `embed` and to the `MultiTurnAgent`
+#[derive(Clone, Default)]
+pub struct UsageTracker {
+    state: Arc<Mutex<TrackerState>>,
+    prices: Arc<PriceTable>,
+    budget: Budget,
+}
+
+impl UsageTracker {
+    pub fn new(prices: PriceTable) -> Self {
+        Self {
+            prices: Arc::new(prices),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_budget(mut self, budget: Budget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    pub fn record(&self, model: &str, usage: Usage) {
+        let mut state = self.state.lock().expect("usage tracker poisoned");
+        *state.per_model.entry(model.to_string()).or_default() += usage;
+    }
+
+    /// stores the usage of a whole `multi_turn_prompt`, on top of what was `record`ed
+    pub fn record_turn(&self, usage: Usage) {
+        let mut state = self.state.lock().expect("usage tracker poisoned");
+        state.turns.push(usage);
+    }
+
+    pub fn turns(&self) -> Vec<Usage> {
+        self.state
+            .lock()
+            .expect("usage tracker poisoned")
+            .turns
+            .clone()
+    }
+
+    pub fn per_model(&self) -> HashMap<String, Usage> {
+        self.state
+            .lock()
+            .expect("usage tracker poisoned")
+            .per_model
+            .clone()
+    }
+
+    pub fn total(&self) -> Usage {
+        self.per_model()
+            .into_values()
+            .fold(Usage::default(), |total, usage| total + usage)
+    }
+
+    /// in USD
+    pub fn cost(&self) -> f64 {
+        self.per_model()
+            .iter()
+            .map(|(model, usage)| self.prices.cost(model, usage))
+            .sum()
+    }
+
+    pub fn check_budget(&self) -> Result<(), BudgetExceeded> {
+        let tokens = self.total().total_tokens();
+        let cost = self.cost();
+
+        let over_tokens = self.budget.max_tokens.is_some_and(|max| tokens > max);
+        let over_cost = self.budget.max_cost.is_some_and(|max| cost > max);
+
+        if over_tokens || over_cost {
+            return Err(BudgetExceeded {
+                tokens,
+                cost,
+                budget: self.budget,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn priced(budget: Budget) -> UsageTracker {
+        let price = ModelPrice {
+            prompt: 1.0,
+            completion: 4.0,
+            embedding: 0.5,
+        };
+        UsageTracker::new(PriceTable::new().with_model("priced", price)).with_budget(budget)
+    }
+
+    fn usage(prompt_tokens: u64, completion_tokens: u64, embedding_tokens: u64) -> Usage {
+        Usage {
+            prompt_tokens,
+            completion_tokens,
+            embedding_tokens,
+        }
+    }
+
+    #[test]
+    fn costs_add_up_per_model() {
+        let tracker = priced(Budget::default());
+        tracker.record("priced", usage(1_000_000, 500_000, 0));
+        tracker.record("priced", usage(0, 0, 2_000_000));
+        // tracked, but free
+        tracker.record("unknown", usage(1_000_000, 1_000_000, 0));
+
+        assert!((tracker.cost() - 4.0).abs() < 1e-9);
+        assert_eq!(tracker.total(), usage(2_000_000, 1_500_000, 2_000_000));
+        assert_eq!(tracker.per_model()["unknown"].total_tokens(), 2_000_000);
+    }
+
+    #[test]
+    fn budget_is_checked_against_tokens_and_cost() {
+        let tracker = priced(Budget {
+            max_tokens: Some(1000),
+            max_cost: None,
+        });
+        tracker.record("unknown", usage(600, 400, 0));
+        assert!(tracker.check_budget().is_ok());
+        tracker.record("unknown", usage(0, 0, 1));
+        let exceeded = tracker.check_budget().unwrap_err();
+        assert_eq!(exceeded.tokens, 1001);
+
+        let tracker = priced(Budget {
+            max_tokens: None,
+            max_cost: Some(0.01),
+        });
+        tracker.record("priced", usage(10_000, 0, 0));
+        assert!(tracker.check_budget().is_ok());
+        tracker.record("priced", usage(0, 1, 0));
+        let exceeded = tracker.check_budget().unwrap_err();
+        assert!(exceeded.cost > 0.01);
+
+        // no budget, no limit
+        let unlimited = UsageTracker::new(PriceTable::new());
+        unlimited.record("priced", usage(u32::MAX.into(), 0, 0));
+        assert!(unlimited.check_budget().is_ok());
+    }
+}
@@ -0,0 +1,377 @@
+use serde_json::{Map, Value};
+
+/// why a tool call's arguments were rejected, worded for the model so it can fix the
+/// call itself
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid arguments for `{tool}`: {}", errors.join("; "))]
+pub struct ValidationError {
+    pub tool: String,
+    pub errors: Vec<String>,
+}
+
+/// the arguments after validation, along with every coercion applied to them
+#[derive(Debug, Clone)]
+pub struct Validated {
+    pub arguments: Value,
+    /// e.g. "`x`: converted the string \"5\" to a number"
+    pub repairs: Vec<String>,
+}
+
+/// checks `arguments` against a tool's parameters schema (the subset our tools generate:
+/// objects, arrays, numbers, integers, strings, booleans, enums and ranges), coercing
+/// the cases that are safe to fix:
+///
+/// - numeric strings where a number is expected (`"5"` -> `5`), unless they have more
+///   digits than a float keeps, those are left for the tool to read exactly
+/// - `"true"` / `"false"` where a boolean is expected
+/// - a single-element array where a single value is expected, and the other way around
+/// - enum values in the wrong case (`"Credit"` -> `"credit"`)
+/// - `null` for optional fields, which are dropped
+pub fn validate(
+    tool: &str,
+    schema: &Value,
+    arguments: Value,
+) -> Result<Validated, ValidationError> {
+    let mut validator = Validator::default();
+    let arguments = validator.value("", schema, arguments);
+
+    if validator.errors.is_empty() {
+        Ok(Validated {
+            arguments,
+            repairs: validator.repairs,
+        })
+    } else {
+        Err(ValidationError {
+            tool: tool.to_string(),
+            errors: validator.errors,
+        })
+    }
+}
+
+#[derive(Default)]
+struct Validator {
+    errors: Vec<String>,
+    repairs: Vec<String>,
+}
+
+impl Validator {
+    fn error(&mut self, path: &str, message: impl std::fmt::Display) {
+        self.errors
+            .push(format!("`{}`: {message}", display_path(path)));
+    }
+
+    fn repair(&mut self, path: &str, message: impl std::fmt::Display) {
+        self.repairs
+            .push(format!("`{}`: {message}", display_path(path)));
+    }
+
+    fn value(&mut self, path: &str, schema: &Value, value: Value) -> Value {
+        let Some(expected) = schema.get("type").and_then(Value::as_str) else {
+            return value;
+        };
+
+        let value = match (expected, value) {
+            ("array", Value::Array(items)) => Value::Array(items),
+            ("array", value) => {
+                self.repair(path, "wrapped the single value in an array");
+                Value::Array(vec![value])
+            }
+            (_, Value::Array(mut items)) if items.len() == 1 => {
+                self.repair(path, "unwrapped the single-element array");
+                items.remove(0)
+            }
+            (_, value) => value,
+        };
+
+        let value = match expected {
+            "object" => self.object(path, schema, value),
+            "array" => self.array(path, schema, value),
+            "number" | "integer" => self.number(path, expected, value),
+            "boolean" => self.boolean(path, value),
+            "string" => self.string(path, schema, value),
+            _ => value,
+        };
+
+        let number = match &value {
+            Value::String(text) if expected == "number" => text.trim().parse().ok(),
+            _ => value.as_f64(),
+        };
+        if let Some(number) = number {
+            self.range(path, schema, number);
+        }
+        value
+    }
+
+    fn object(&mut self, path: &str, schema: &Value, value: Value) -> Value {
+        let Value::Object(mut object) = value else {
+            self.error(
+                path,
+                format!("expected an object, got {}", describe(&value)),
+            );
+            return value;
+        };
+        let properties = schema.get("properties").and_then(Value::as_object);
+        let required: Vec<&str> = schema
+            .get("required")
+            .and_then(Value::as_array)
+            .map(|required| required.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+
+        for name in &required {
+            if object.get(*name).is_none_or(Value::is_null) {
+                self.error(&join(path, name), "is required");
+            }
+        }
+
+        let mut validated = Map::new();
+        for (name, value) in std::mem::take(&mut object) {
+            let field_path = join(path, &name);
+            if value.is_null() {
+                if !required.contains(&name.as_str()) {
+                    self.repair(&field_path, "dropped the null optional value");
+                }
+                continue;
+            }
+            // unknown fields are left for the tool to ignore
+            let value = match properties.and_then(|properties| properties.get(&name)) {
+                Some(schema) => self.value(&field_path, schema, value),
+                None => value,
+            };
+            validated.insert(name, value);
+        }
+
+        Value::Object(validated)
+    }
+
+    fn array(&mut self, path: &str, schema: &Value, value: Value) -> Value {
+        let Value::Array(items) = value else {
+            return value;
+        };
+        let Some(item_schema) = schema.get("items") else {
+            return Value::Array(items);
+        };
+
+        Value::Array(
+            items
+                .into_iter()
+                .enumerate()
+                .map(|(i, item)| self.value(&format!("{path}[{i}]"), item_schema, item))
+                .collect(),
+        )
+    }
+
+    fn number(&mut self, path: &str, expected: &str, value: Value) -> Value {
+        let number = match &value {
+            Value::Number(number) => number.as_f64(),
+            Value::String(text) => {
+                let parsed = text.trim().parse::<f64>().ok().filter(|n| n.is_finite());
+                if parsed.is_some() && expected == "number" && exceeds_f64(text) {
+                    return value;
+                }
+                if parsed.is_some() {
+                    self.repair(path, format!("converted the string {value} to a number"));
+                }
+                parsed
+            }
+            _ => None,
+        };
+
+        let Some(number) = number else {
+            self.error(
+                path,
+                format!("expected a {expected}, got {}", describe(&value)),
+            );
+            return value;
+        };
+        if expected == "integer" && number.fract() != 0.0 {
+            self.error(path, format!("expected an integer, got {number}"));
+        }
+
+        match value {
+            Value::Number(_) => value,
+            // whole numbers stay integers, so they still fit integer fields
+            _ if number.fract() == 0.0 && number.abs() < 9e15 => Value::from(number as i64),
+            _ => serde_json::Number::from_f64(number).map_or(value, Value::Number),
+        }
+    }
+
+    fn boolean(&mut self, path: &str, value: Value) -> Value {
+        match &value {
+            Value::Bool(_) => value,
+            Value::String(text) if matches!(text.trim(), "true" | "false") => {
+                self.repair(path, format!("converted the string {value} to a boolean"));
+                Value::Bool(text.trim() == "true")
+            }
+            _ => {
+                self.error(
+                    path,
+                    format!("expected a boolean, got {}", describe(&value)),
+                );
+                value
+            }
+        }
+    }
+
+    fn string(&mut self, path: &str, schema: &Value, value: Value) -> Value {
+        let Value::String(text) = &value else {
+            self.error(path, format!("expected a string, got {}", describe(&value)));
+            return value;
+        };
+        let Some(options) = schema.get("enum").and_then(Value::as_array) else {
+            return value;
+        };
+
+        let options: Vec<&str> = options.iter().filter_map(Value::as_str).collect();
+        if options.contains(&text.as_str()) {
+            return value;
+        }
+        match options
+            .iter()
+            .find(|option| option.eq_ignore_ascii_case(text.trim()))
+        {
+            Some(option) => {
+                self.repair(path, format!("matched {value} to \"{option}\""));
+                Value::String(option.to_string())
+            }
+            None => {
+                self.error(
+                    path,
+                    format!("expected one of {}, got {value}", options.join(", ")),
+                );
+                value
+            }
+        }
+    }
+
+    fn range(&mut self, path: &str, schema: &Value, number: f64) {
+        let bound = |key: &str| schema.get(key).and_then(Value::as_f64);
+        if let Some(minimum) = bound("minimum").filter(|minimum| number < *minimum) {
+            self.error(path, format!("must be at least {minimum}, got {number}"));
+        }
+        if let Some(maximum) = bound("maximum").filter(|maximum| number > *maximum) {
+            self.error(path, format!("must be at most {maximum}, got {number}"));
+        }
+    }
+}
+
+/// whether a numeric string has more significant digits than an `f64` holds
+fn exceeds_f64(text: &str) -> bool {
+    let text = text.trim().trim_start_matches(['+', '-']);
+    let mantissa = text.split(['e', 'E']).next().unwrap_or_default();
+    let digits: String = mantissa.chars().filter(char::is_ascii_digit).collect();
+    digits.trim_start_matches('0').trim_end_matches('0').len() > 15
+}
+
+fn join(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{path}.{name}")
+    }
+}
+
+fn display_path(path: &str) -> &str {
+    if path.is_empty() { "arguments" } else { path }
+}
+
+fn describe(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Bool(_) => format!("the boolean {value}"),
+        Value::Number(_) => format!("the number {value}"),
+        Value::String(_) => format!("the string {value}"),
+        Value::Array(_) => "an array".to_string(),
+        Value::Object(_) => "an object".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "x": { "type": "number", "minimum": 0 },
+                "places": { "type": "integer" },
+                "values": { "type": "array", "items": { "type": "number" } },
+                "exact": { "type": "boolean" },
+                "mode": { "type": "string", "enum": ["half_even", "floor"] },
+                "note": { "type": "string" }
+            },
+            "required": ["x"]
+        })
+    }
+
+    #[test]
+    fn repairs_harmless_mistakes() {
+        let validated = validate(
+            "round",
+            &schema(),
+            json!({
+                "x": " 5.5 ",
+                "places": "2",
+                "values": 3,
+                "exact": "true",
+                "mode": "Floor",
+                "note": null
+            }),
+        )
+        .unwrap();
+
+        assert_eq!(
+            validated.arguments,
+            json!({ "x": 5.5, "places": 2, "values": [3], "exact": true, "mode": "floor" })
+        );
+        assert_eq!(validated.repairs.len(), 6);
+        assert!(
+            validated
+                .repairs
+                .contains(&"`x`: converted the string \" 5.5 \" to a number".to_string())
+        );
+    }
+
+    #[test]
+    fn leaves_numbers_a_float_cant_hold_as_strings() {
+        let exact = "0.3333333333333333333333333333";
+        let validated = validate("add", &schema(), json!({ "x": exact })).unwrap();
+        assert_eq!(validated.arguments, json!({ "x": exact }));
+        assert!(validated.repairs.is_empty());
+
+        let error = validate("add", &schema(), json!({ "x": format!("-{exact}") })).unwrap_err();
+        assert!(error.to_string().contains("must be at least 0"));
+    }
+
+    #[test]
+    fn counts_significant_digits() {
+        assert!(!exceeds_f64("123456789012345"));
+        assert!(!exceeds_f64("0.000000000000000000001"));
+        assert!(!exceeds_f64("1000000000000000000000"));
+        assert!(exceeds_f64("1234567890.123456"));
+        assert!(exceeds_f64("-1.2345678901234567e10"));
+    }
+
+    #[test]
+    fn reports_every_error() {
+        let error = validate(
+            "round",
+            &schema(),
+            json!({ "places": 1.5, "mode": "up", "values": ["a"] }),
+        )
+        .unwrap_err();
+
+        assert_eq!(error.tool, "round");
+        assert_eq!(
+            error.errors,
+            [
+                "`x`: is required",
+                "`mode`: expected one of half_even, floor, got \"up\"",
+                "`places`: expected an integer, got 1.5",
+                "`values[0]`: expected a number, got the string \"a\"",
+            ]
+        );
+    }
+}